@@ -0,0 +1,177 @@
+use crate::{
+    challenge::{Challenge, ChallengeSource, Rng, COLOR_NAME_KEYS, PALETTE},
+    locale::Catalog,
+    voxel::{VoxelGrid, GRID_SIZE},
+};
+
+/// Mixed into the challenge seed so the questions don't just repeat the draws that built the structure
+const QUESTION_SEED: u64 = 0x7E47_C4A7_C4A0_0001;
+
+/// Questions asked per text challenge
+pub const QUESTION_COUNT: usize = 3;
+
+/// A question about the described structure, answered by typing a number or a color name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Question {
+    pub prompt: String,
+    answer: String,
+}
+
+impl Question {
+    /// Whether `answer` matches, ignoring case and surrounding whitespace
+    pub fn check(&self, answer: &str) -> bool {
+        answer.trim().to_lowercase() == self.answer.to_lowercase()
+    }
+}
+
+/// Text version of a challenge for users who can't see the grid. The same seeded structure is described layer by layer and the user answers questions about it instead of building. Written in the catalog's language, and color answers are expected in it too
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChallenge {
+    /// One line per layer from the floor up to the highest block
+    pub layers: Vec<String>,
+    pub questions: Vec<Question>,
+}
+
+impl TextChallenge {
+    pub fn new(challenge: &Challenge, catalog: Catalog) -> Self {
+        let seed = match challenge.spec.source {
+            ChallengeSource::Seed(seed) => seed,
+            ChallengeSource::File(_) => 0,
+        };
+        let mut rng = Rng::new(seed ^ QUESTION_SEED);
+        let blocks = blocks(challenge.structure());
+        let top = blocks.iter().map(|block| block.layer).max().unwrap_or(0);
+        let layers = (0..top)
+            .map(|layer| describe_layer(catalog, &blocks, layer))
+            .collect();
+        let questions = if blocks.is_empty() {
+            Vec::new()
+        } else {
+            (0..QUESTION_COUNT)
+                .map(|index| ask(catalog, &mut rng, &blocks, index))
+                .collect()
+        };
+        Self { layers, questions }
+    }
+
+    /// Whether every question was answered correctly. Missing answers count as wrong, and a challenge without questions can't be passed
+    pub fn verify(&self, answers: &[String]) -> bool {
+        !self.questions.is_empty()
+            && self.questions.len() == answers.len()
+            && self
+                .questions
+                .iter()
+                .zip(answers)
+                .all(|(question, answer)| question.check(answer))
+    }
+}
+
+/// A solid cell in the numbering read out to the user: layers, rows and columns all start at 1
+#[derive(Debug, Clone, Copy)]
+struct Block {
+    layer: usize,
+    row: usize,
+    column: usize,
+    /// Index into [`PALETTE`]
+    color: usize,
+}
+
+fn blocks(grid: &VoxelGrid) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    for y in 0..GRID_SIZE {
+        for z in 0..GRID_SIZE {
+            for x in 0..GRID_SIZE {
+                let voxel = grid.get(x, y, z);
+                if !voxel.is_solid() {
+                    continue;
+                }
+                // Files may use colors outside the palette. Those are read out as the closest palette color
+                let color = PALETTE
+                    .iter()
+                    .enumerate()
+                    .min_by(|(_, a), (_, b)| {
+                        a.distance_squared(voxel.color)
+                            .total_cmp(&b.distance_squared(voxel.color))
+                    })
+                    .map_or(0, |(index, _)| index);
+                blocks.push(Block {
+                    layer: y + 1,
+                    row: z + 1,
+                    column: x + 1,
+                    color,
+                });
+            }
+        }
+    }
+    blocks
+}
+
+fn describe_layer(catalog: Catalog, blocks: &[Block], layer: usize) -> String {
+    let cells: Vec<String> = blocks
+        .iter()
+        .filter(|block| block.layer == layer + 1)
+        .map(|block| {
+            catalog.format(
+                "text-block",
+                &[
+                    ("color", &catalog.get(COLOR_NAME_KEYS[block.color])),
+                    ("row", &block.row),
+                    ("column", &block.column),
+                ],
+            )
+        })
+        .collect();
+    let name = if layer == 0 {
+        catalog.get("text-layer-floor").to_owned()
+    } else {
+        catalog.format("text-layer", &[("layer", &(layer + 1))])
+    };
+    if cells.is_empty() {
+        catalog.format("text-layer-empty", &[("layer", &name)])
+    } else {
+        let blocks = cells.join("; ");
+        catalog.format(
+            "text-layer-blocks",
+            &[("layer", &name), ("blocks", &blocks)],
+        )
+    }
+}
+
+/// Rotates through the kinds of question so a challenge never asks the same kind twice in a row
+fn ask(catalog: Catalog, rng: &mut Rng, blocks: &[Block], index: usize) -> Question {
+    let block = blocks[rng.below(blocks.len())];
+    let color = catalog.get(COLOR_NAME_KEYS[block.color]);
+    match index % 3 {
+        0 => Question {
+            prompt: catalog.format(
+                "question-color",
+                &[
+                    ("layer", &block.layer),
+                    ("row", &block.row),
+                    ("column", &block.column),
+                ],
+            ),
+            answer: color.to_owned(),
+        },
+        1 => {
+            let count = blocks
+                .iter()
+                .filter(|other| other.layer == block.layer && other.color == block.color)
+                .count();
+            Question {
+                prompt: catalog.format(
+                    "question-layer-count",
+                    &[("color", &color), ("layer", &block.layer)],
+                ),
+                answer: count.to_string(),
+            }
+        }
+        _ => {
+            let count = blocks.iter().filter(|other| other.row == block.row).count();
+            Question {
+                prompt: catalog.format("question-row-count", &[("row", &block.row)]),
+                answer: count.to_string(),
+            }
+        }
+    }
+}
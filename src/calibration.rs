@@ -0,0 +1,128 @@
+use std::fmt;
+
+use crate::{
+    challenge::{
+        occluded_blocks, Challenge, ChallengeError, ChallengeKind, ChallengeSource, ChallengeSpec,
+        Solution,
+    },
+    voxel::VoxelGrid,
+};
+
+/// Averages over every generated challenge of one kind
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KindMetrics {
+    pub challenges: usize,
+    /// Placements and removals needed to get from the starting grid to the solution in place. Typed answers count as no edits
+    pub edits: f64,
+    /// Solid blocks in the structure the user looks at
+    pub blocks: f64,
+    /// Blocks hidden behind others from the starting view
+    pub occluded: f64,
+    /// Distinct colors in the structure the user looks at
+    pub colors: f64,
+}
+
+/// Metrics for many challenges generated with the same difficulty, used to tune [`crate::difficulty::DifficultyParams`] offline
+#[derive(Debug, Clone)]
+pub struct CalibrationReport {
+    pub spec: ChallengeSpec,
+    pub kinds: Vec<(ChallengeKind, KindMetrics)>,
+}
+
+impl CalibrationReport {
+    /// Generates `count` challenges of every kind the spec allows, with consecutive seeds starting from the spec's seed
+    pub fn generate(spec: &ChallengeSpec, count: usize) -> Result<Self, ChallengeError> {
+        let first_seed = match spec.source {
+            ChallengeSource::Seed(seed) => seed,
+            // Files always produce the same challenge, so there is nothing to average
+            ChallengeSource::File(_) => 0,
+        };
+        let kinds = match spec.kind {
+            Some(kind) => vec![kind],
+            None => ChallengeKind::ALL.to_vec(),
+        };
+        let mut report = Self {
+            spec: spec.clone(),
+            kinds: Vec::new(),
+        };
+        for kind in kinds {
+            let mut metrics = KindMetrics::default();
+            for offset in 0..count as u64 {
+                let challenge = Challenge::new(ChallengeSpec {
+                    source: ChallengeSource::Seed(first_seed.wrapping_add(offset)),
+                    kind: Some(kind),
+                    ..spec.clone()
+                })?;
+                let shown = if challenge.start.solid_count() > 0 {
+                    &challenge.start
+                } else {
+                    &challenge.target
+                };
+                metrics.edits += edits_to_solve(&challenge) as f64;
+                metrics.blocks += shown.solid_count() as f64;
+                metrics.occluded += occluded_blocks(shown) as f64;
+                metrics.colors += distinct_colors(shown) as f64;
+            }
+            let total = count.max(1) as f64;
+            metrics.challenges = count;
+            metrics.edits /= total;
+            metrics.blocks /= total;
+            metrics.occluded /= total;
+            metrics.colors /= total;
+            report.kinds.push((kind, metrics));
+        }
+        Ok(report)
+    }
+}
+
+impl fmt::Display for CalibrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Difficulty: {:?}", self.spec.difficulty)?;
+        writeln!(
+            f,
+            "{:<12} {:>10} {:>8} {:>8} {:>8} {:>8}",
+            "kind", "challenges", "edits", "blocks", "occluded", "colors"
+        )?;
+        for (kind, metrics) in &self.kinds {
+            writeln!(
+                f,
+                "{:<12} {:>10} {:>8.2} {:>8.2} {:>8.2} {:>8.2}",
+                format!("{kind:?}"),
+                metrics.challenges,
+                metrics.edits,
+                metrics.blocks,
+                metrics.occluded,
+                metrics.colors
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Cells that differ between the starting grid and the solution. Recoloring a block takes a removal and a placement
+fn edits_to_solve(challenge: &Challenge) -> usize {
+    let Solution::Grid(solution) = &challenge.solution else {
+        return 0;
+    };
+    challenge
+        .start
+        .voxels
+        .iter()
+        .zip(&solution.voxels)
+        .map(|(start, end)| match (start.is_solid(), end.is_solid()) {
+            (true, true) if start.color != end.color => 2,
+            (true, true) | (false, false) => 0,
+            _ => 1,
+        })
+        .sum()
+}
+
+fn distinct_colors(grid: &VoxelGrid) -> usize {
+    let mut colors = Vec::new();
+    for voxel in grid.voxels.iter().filter(|voxel| voxel.is_solid()) {
+        if !colors.contains(&voxel.color) {
+            colors.push(voxel.color);
+        }
+    }
+    colors.len()
+}
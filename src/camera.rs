@@ -0,0 +1,253 @@
+use std::{
+    f64::consts::FRAC_PI_4,
+    time::{Duration, Instant},
+};
+
+use glam::{DMat3, DMat4, DQuat, DVec3};
+use transform_gizmo_egui::{
+    math::Transform,
+    mint::{ColumnMatrix4, Quaternion, RowMatrix4},
+};
+
+/// Converts any matrix that mint understands into the row major layout the gizmo expects
+pub fn to_row_matrix<M: Into<ColumnMatrix4<f64>>>(matrix: M) -> RowMatrix4<f64> {
+    matrix.into().into()
+}
+
+/// Converts the gizmo's rotation into glam. This and [`to_gizmo_transform`] are the only places the gizmo's math types cross into the rest of the crate
+pub fn from_gizmo_rotation(rotation: Quaternion<f64>) -> DQuat {
+    DQuat::from(rotation).normalize()
+}
+
+/// The gizmo transform for a grid turned by `rotation`. The grid is never scaled or moved, so only the rotation is carried over
+pub fn to_gizmo_transform(rotation: DQuat) -> Transform {
+    Transform::from_scale_rotation_translation(DVec3::ONE, rotation, DVec3::ZERO)
+}
+
+/// Canonical orientations of the grid, named after the face turned towards the camera
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewPreset {
+    Front,
+    Back,
+    Left,
+    Right,
+    Top,
+    Bottom,
+    /// The starting view, looking down the diagonal
+    Isometric,
+}
+
+impl ViewPreset {
+    pub const ALL: [ViewPreset; 7] = [
+        ViewPreset::Front,
+        ViewPreset::Back,
+        ViewPreset::Left,
+        ViewPreset::Right,
+        ViewPreset::Top,
+        ViewPreset::Bottom,
+        ViewPreset::Isometric,
+    ];
+
+    /// Catalog key of the preset's button label
+    pub fn label_key(self) -> &'static str {
+        match self {
+            ViewPreset::Front => "view-front",
+            ViewPreset::Back => "view-back",
+            ViewPreset::Left => "view-left",
+            ViewPreset::Right => "view-right",
+            ViewPreset::Top => "view-top",
+            ViewPreset::Bottom => "view-bottom",
+            ViewPreset::Isometric => "view-isometric",
+        }
+    }
+
+    /// Grid rotation that turns this preset's face straight towards the camera with the grid's up (or front, for top and bottom) pointing up on screen
+    pub fn rotation(self, camera: &Camera) -> DQuat {
+        // Face normal and the grid direction that should end up pointing up on screen
+        let (face, face_up) = match self {
+            ViewPreset::Front => (DVec3::Z, DVec3::Y),
+            ViewPreset::Back => (DVec3::NEG_Z, DVec3::Y),
+            ViewPreset::Left => (DVec3::NEG_X, DVec3::Y),
+            ViewPreset::Right => (DVec3::X, DVec3::Y),
+            ViewPreset::Top => (DVec3::Y, DVec3::NEG_Z),
+            ViewPreset::Bottom => (DVec3::NEG_Y, DVec3::Z),
+            ViewPreset::Isometric => return DQuat::IDENTITY,
+        };
+        let to_eye = camera.view_direction();
+        let screen_up = (camera.up - to_eye * to_eye.dot(camera.up)).normalize();
+        let from = DMat3::from_cols(face, face_up, face.cross(face_up));
+        let to = DMat3::from_cols(to_eye, screen_up, to_eye.cross(screen_up));
+        DQuat::from_mat3(&(to * from.transpose())).normalize()
+    }
+}
+
+/// Smoothly turns the grid from one orientation to another
+#[derive(Debug, Clone, Copy)]
+pub struct RotationAnimation {
+    from: DQuat,
+    to: DQuat,
+    start: Instant,
+}
+
+impl RotationAnimation {
+    const DURATION: Duration = Duration::from_millis(350);
+
+    pub fn new(from: DQuat, to: DQuat) -> Self {
+        Self {
+            from,
+            to,
+            start: Instant::now(),
+        }
+    }
+
+    /// Rotation the animation ends at
+    pub fn target(&self) -> DQuat {
+        self.to
+    }
+
+    /// Rotation at `now` and whether the animation has finished
+    pub fn sample(&self, now: Instant) -> (DQuat, bool) {
+        let t = (now.saturating_duration_since(self.start).as_secs_f64()
+            / Self::DURATION.as_secs_f64())
+        .min(1.0);
+        // Smoothstep so the turn eases in and out
+        let eased = t * t * (3.0 - 2.0 * t);
+        (self.from.slerp(self.to, eased), t >= 1.0)
+    }
+}
+
+/// Perspective projection parameters. The matrix is cached until one of them changes
+#[derive(Debug, Clone)]
+pub struct Projection {
+    fov_y: f64,
+    aspect_ratio: f64,
+    // Clipping planes. Cannot be the same and near must be above 0
+    near: f64,
+    far: f64,
+    matrix: Option<DMat4>,
+}
+
+impl Projection {
+    pub fn new(fov_y: f64, near: f64, far: f64) -> Self {
+        Self {
+            fov_y,
+            aspect_ratio: 1.0,
+            near,
+            far,
+            matrix: None,
+        }
+    }
+
+    /// Updates the aspect ratio (width / height). Degenerate ratios from zero sized viewports are ignored
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f64) {
+        if aspect_ratio.is_finite() && aspect_ratio > 0.0 && aspect_ratio != self.aspect_ratio {
+            self.aspect_ratio = aspect_ratio;
+            self.matrix = None;
+        }
+    }
+
+    pub fn matrix(&mut self) -> DMat4 {
+        let (fov_y, aspect_ratio, near, far) = (self.fov_y, self.aspect_ratio, self.near, self.far);
+        *self
+            .matrix
+            .get_or_insert_with(|| DMat4::perspective_rh(fov_y, aspect_ratio, near, far))
+    }
+}
+
+/// The camera looking at the voxel grid. The view matrix is shared by the gizmo and the raymarcher, each with its own projection since their viewports differ
+#[derive(Debug, Clone)]
+pub struct Camera {
+    eye: DVec3,
+    target: DVec3,
+    up: DVec3,
+    view: Option<DMat4>,
+    /// Projection for the full window scene
+    pub projection: Projection,
+    /// Projection for the gizmo widget in the controls window
+    pub gizmo_projection: Projection,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            // Looking down the diagonal at 45-degree angles towards the origin
+            eye: DVec3::ONE.normalize(),
+            target: DVec3::ZERO,
+            // World "up" direction
+            up: DVec3::Y,
+            view: None,
+            // The far plane sits well past the eye distance so nothing around the origin is clipped
+            projection: Projection::new(FRAC_PI_4, 0.1, 100.0),
+            gizmo_projection: Projection::new(FRAC_PI_4, 0.1, 100.0),
+        }
+    }
+}
+
+impl Camera {
+    /// Closest and furthest the eye may get to the target when zooming
+    const DISTANCE_RANGE: (f64, f64) = (0.3, 5.0);
+
+    /// Moves the camera. The view matrix is only rebuilt if something actually changed
+    pub fn look_at(&mut self, eye: DVec3, target: DVec3) {
+        if eye != self.eye || target != self.target {
+            self.eye = eye;
+            self.target = target;
+            self.view = None;
+        }
+    }
+
+    /// Puts the eye and target back where they started. Projections keep their aspect ratios
+    pub fn reset_view(&mut self) {
+        let default = Camera::default();
+        self.look_at(default.eye, default.target);
+    }
+
+    /// Moves the eye towards (factor < 1) or away from (factor > 1) the target
+    pub fn zoom(&mut self, factor: f64) {
+        let offset = self.eye - self.target;
+        let (min, max) = Self::DISTANCE_RANGE;
+        let distance = (offset.length() * factor).clamp(min, max);
+        self.look_at(self.target + offset.normalize() * distance, self.target);
+    }
+
+    /// Slides the eye and target together across the view plane. Amounts are in world units
+    pub fn pan(&mut self, right: f64, up: f64) {
+        let forward = (self.target - self.eye).normalize();
+        let right_axis = self.right();
+        let up_axis = right_axis.cross(forward);
+        let offset = right_axis * right + up_axis * up;
+        self.look_at(self.eye + offset, self.target + offset);
+    }
+
+    /// Direction from the target towards the eye
+    pub fn view_direction(&self) -> DVec3 {
+        (self.eye - self.target).normalize()
+    }
+
+    /// Camera right direction in world space
+    pub fn right(&self) -> DVec3 {
+        (self.target - self.eye).cross(self.up).normalize()
+    }
+
+    /// World units covered by one pixel at the target's depth, for a viewport `height` pixels tall
+    pub fn world_units_per_pixel(&self, height: f64) -> f64 {
+        let distance = (self.eye - self.target).length();
+        2.0 * distance * (self.projection.fov_y / 2.0).tan() / height.max(1.0)
+    }
+
+    pub fn view_matrix(&mut self) -> DMat4 {
+        let (eye, target, up) = (self.eye, self.target, self.up);
+        *self
+            .view
+            .get_or_insert_with(|| DMat4::look_at_rh(eye, target, up))
+    }
+
+    /// View and projection matrices for a gizmo drawn in a viewport with the given aspect ratio
+    pub fn gizmo_matrices(&mut self, aspect_ratio: f64) -> (RowMatrix4<f64>, RowMatrix4<f64>) {
+        self.gizmo_projection.set_aspect_ratio(aspect_ratio);
+        (
+            to_row_matrix(self.view_matrix()),
+            to_row_matrix(self.gizmo_projection.matrix()),
+        )
+    }
+}
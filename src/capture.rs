@@ -0,0 +1,529 @@
+use std::{
+    f64::consts::TAU,
+    fmt,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use glam::DQuat;
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, Frame, ImageError, ImageFormat, RgbaImage,
+};
+use wgpu::{
+    BindGroupLayout, Buffer, BufferAsyncError, Device, Queue, RenderPipeline, Texture,
+    TextureFormat, TextureView,
+};
+
+use crate::{
+    camera::Camera,
+    challenge::Challenge,
+    cli::Args,
+    egui_render::AppState,
+    error,
+    palette::PalettePreset,
+    theme::Theme,
+    uniforms::{CameraUniform, PaletteUniform, ScreenUniform, UniformBindings},
+    voxel::VoxelGrid,
+    wgpu::{create_instance, create_render_pipeline, request_device, select_adapter},
+};
+
+/// Width and height of captures made from the command line when --width and --height aren't given
+pub const CAPTURE_SIZE: u32 = 512;
+/// How long one full turn of a turntable takes, however many frames it has
+const TURNTABLE_DURATION: Duration = Duration::from_secs(4);
+/// Trades GIF color quality for encoding time, from 1 (best) to 30 (fastest)
+const GIF_SPEED: i32 = 10;
+
+/// Reasons a capture couldn't be made or saved
+#[derive(Debug)]
+pub enum CaptureError {
+    /// The rendered frame couldn't be copied back from the GPU
+    Readback(BufferAsyncError),
+    /// The image couldn't be encoded or written
+    Write(PathBuf, ImageError),
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureError::Readback(e) => write!(f, "Couldn't read the capture back: {e}"),
+            CaptureError::Write(path, e) => write!(f, "Couldn't save {}: {e}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CaptureError::Readback(e) => Some(e),
+            CaptureError::Write(_, e) => Some(e),
+        }
+    }
+}
+
+/// What a capture shows. Copied from the app state so capturing doesn't disturb the view on screen
+#[derive(Debug, Clone)]
+pub struct CaptureView {
+    pub grid: VoxelGrid,
+    pub camera: Camera,
+    pub rotation: DQuat,
+    pub palette: PalettePreset,
+    pub patterns: bool,
+    pub background: wgpu::Color,
+}
+
+impl CaptureView {
+    /// The scene as it is on screen, without the cursor and controls
+    pub fn from_app_state(app_state: &AppState) -> Self {
+        Self {
+            grid: app_state.grid,
+            camera: app_state.camera.clone(),
+            rotation: app_state.rotation,
+            palette: app_state.palette,
+            patterns: app_state.patterns,
+            background: app_state.theme.clear_color(),
+        }
+    }
+}
+
+/// Single frames and turntables, picked with F12 and Shift+F12 in the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureKind {
+    Screenshot,
+    Turntable,
+}
+
+impl CaptureKind {
+    /// File in the working directory named after the current time, so captures don't overwrite each other
+    pub fn default_path(self) -> PathBuf {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let extension = match self {
+            CaptureKind::Screenshot => "png",
+            CaptureKind::Turntable => "gif",
+        };
+        PathBuf::from(format!("minecaptcha-{seconds}.{extension}"))
+    }
+}
+
+/// Offscreen render target the scene is drawn into with its own pipeline, then copied back to the CPU
+pub struct Capture {
+    texture: Texture,
+    view: TextureView,
+    /// Receives the texture. Rows are padded to the alignment WGPU needs for copies
+    buffer: Buffer,
+    bindings: UniformBindings,
+    pipeline: RenderPipeline,
+    width: u32,
+    height: u32,
+}
+
+impl Capture {
+    /// Captures are always RGBA in sRGB so they can be saved without converting, whatever format the window's surface has
+    pub const FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+    pub fn new(device: &Device, layout: &BindGroupLayout, width: u32, height: u32) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Capture Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            // Rendered to by the voxel pipeline and then copied into the readback buffer
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Readback Buffer"),
+            size: (padded_row(width) * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let bindings = UniformBindings::new(
+            device,
+            layout,
+            "Capture",
+            ScreenUniform::new(width, height),
+            &VoxelGrid::default(),
+        );
+        Self {
+            texture,
+            view,
+            buffer,
+            bindings,
+            pipeline: create_render_pipeline(device, layout, Self::FORMAT),
+            width,
+            height,
+        }
+    }
+
+    /// Renders `view` and waits for the pixels to come back from the GPU
+    pub fn frame(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        view: &CaptureView,
+    ) -> Result<RgbaImage, CaptureError> {
+        // The on screen camera is set up for the window's shape, which may not be the capture's
+        let mut camera = view.camera.clone();
+        camera
+            .projection
+            .set_aspect_ratio(self.width as f64 / self.height as f64);
+        let camera = CameraUniform::new(&mut camera, &view.grid, view.rotation);
+        self.bindings.write_camera(queue, camera);
+        self.bindings.write_grid(queue, &view.grid);
+        self.bindings
+            .write_palette(queue, PaletteUniform::new(view.palette, view.patterns));
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Capture Encoder"),
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Capture Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(view.background),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &self.bindings.bind_group, &[]);
+            render_pass.draw(0..6, 0..1);
+        }
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &self.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row(self.width)),
+                    rows_per_image: None,
+                },
+            },
+            self.texture.size(),
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = self.buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            // The receiver only goes away if this function already returned
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .unwrap_or(Err(BufferAsyncError))
+            .map_err(CaptureError::Readback)?;
+
+        let row = (self.width * 4) as usize;
+        let mut pixels = Vec::with_capacity(row * self.height as usize);
+        for padded in slice
+            .get_mapped_range()
+            .chunks_exact(padded_row(self.width) as usize)
+        {
+            pixels.extend_from_slice(&padded[..row]);
+        }
+        self.buffer.unmap();
+        // The buffer holds exactly width by height pixels, so this can't fail
+        Ok(RgbaImage::from_raw(self.width, self.height, pixels).expect("capture size"))
+    }
+
+    /// Renders `frames` frames of the grid making one full turn around the gizmo's Y axis, starting from the view's rotation
+    pub fn turntable(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        view: &CaptureView,
+        frames: u32,
+    ) -> Result<Vec<RgbaImage>, CaptureError> {
+        let mut view = view.clone();
+        let start = view.rotation;
+        (0..frames)
+            .map(|frame| {
+                let angle = TAU * frame as f64 / frames as f64;
+                view.rotation = (DQuat::from_rotation_y(angle) * start).normalize();
+                self.frame(device, queue, &view)
+            })
+            .collect()
+    }
+}
+
+/// Bytes per row in the readback buffer. Texture copies need rows aligned to 256 bytes
+fn padded_row(width: u32) -> u32 {
+    (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+}
+
+pub fn write_png(path: &Path, image: &RgbaImage) -> Result<(), CaptureError> {
+    image
+        .save_with_format(path, ImageFormat::Png)
+        .map_err(|e| CaptureError::Write(path.to_path_buf(), e))
+}
+
+/// Saves `frames` as a looping GIF lasting [`TURNTABLE_DURATION`]
+pub fn write_gif(path: &Path, frames: Vec<RgbaImage>) -> Result<(), CaptureError> {
+    let write_error = |e| CaptureError::Write(path.to_path_buf(), e);
+    let file = File::create(path).map_err(|e| write_error(ImageError::IoError(e)))?;
+    let mut encoder = GifEncoder::new_with_speed(BufWriter::new(file), GIF_SPEED);
+    encoder.set_repeat(Repeat::Infinite).map_err(write_error)?;
+    let delay = Delay::from_saturating_duration(TURNTABLE_DURATION / frames.len().max(1) as u32);
+    encoder
+        .encode_frames(
+            frames
+                .into_iter()
+                .map(|image| Frame::from_parts(image, 0, 0, delay)),
+        )
+        .map_err(write_error)
+}
+
+/// GPU objects for rendering captures without a window
+pub struct Headless {
+    pub device: Device,
+    pub queue: Queue,
+    pub layout: BindGroupLayout,
+}
+
+impl Headless {
+    /// Picks an adapter the same way the window does, except it doesn't have to present anywhere
+    pub fn new(args: &Args) -> error::Result<Self> {
+        let instance = create_instance(args);
+        let adapter = select_adapter(&instance, None, args)?;
+        let (device, queue) = request_device(&adapter)?;
+        let layout = UniformBindings::layout(&device);
+        Ok(Self {
+            device,
+            queue,
+            layout,
+        })
+    }
+}
+
+/// Renders the challenge's structure to the files given with --screenshot and --turntable without opening a window
+pub fn run(args: &Args, theme: &Theme) -> error::Result<()> {
+    let Headless {
+        device,
+        queue,
+        layout,
+    } = Headless::new(args)?;
+    let width = args.width.unwrap_or(CAPTURE_SIZE);
+    let height = args.height.unwrap_or(CAPTURE_SIZE);
+    let capture = Capture::new(&device, &layout, width, height);
+    let challenge = Challenge::new(args.challenge_spec())?;
+    let view = CaptureView {
+        grid: *challenge.structure(),
+        camera: Camera::default(),
+        rotation: DQuat::IDENTITY,
+        palette: args.palette,
+        patterns: args.patterns,
+        background: theme.clear_color(),
+    };
+    if let Some(path) = &args.screenshot {
+        write_png(path, &capture.frame(&device, &queue, &view)?)?;
+        log::info!("Saved screenshot to {}", path.display());
+    }
+    if let Some(path) = &args.turntable {
+        let frames = capture.turntable(&device, &queue, &view, args.turntable_frames)?;
+        write_gif(path, frames)?;
+        log::info!("Saved turntable to {}", path.display());
+    }
+    Ok(())
+}
+
+/// Golden image tests for the voxel shader. Each scene is rendered on a software adapter and compared with the PNG of the same name in `tests/golden`. Run with `UPDATE_GOLDEN=1` to write new goldens after an intended change to how things look, and with `SKIP_GOLDEN=1` on hosts that have no software adapter
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        camera::ViewPreset,
+        challenge::PALETTE,
+        voxel::{Voxel, GRID_SIZE},
+    };
+
+    /// Side of every golden image. Small enough for a software adapter to render quickly, big enough to show a block's faces and patterns
+    const GOLDEN_SIZE: u32 = 128;
+    /// CIE76 color difference below which two pixels count as the same. Around 2.3 is the smallest difference people notice
+    const PIXEL_TOLERANCE: f32 = 3.0;
+    /// Share of pixels allowed to differ, for edges that different rasterizers round differently
+    const MAX_DIFFERENT: f64 = 0.005;
+
+    /// A staircase with one block of each palette color, so every color and both vertical and horizontal faces show up
+    fn staircase() -> VoxelGrid {
+        let mut grid = VoxelGrid::default();
+        for (step, color) in PALETTE.into_iter().enumerate() {
+            let x = (step + 1).min(GRID_SIZE - 1);
+            grid.set(x, step / 2, GRID_SIZE / 2, Voxel::solid(color));
+        }
+        grid
+    }
+
+    /// `grid` turned to `preset`, from a camera moved in so the blocks fill more of the small image
+    fn scene(grid: VoxelGrid, preset: ViewPreset) -> CaptureView {
+        let mut camera = Camera::default();
+        camera.zoom(0.6);
+        CaptureView {
+            grid,
+            rotation: preset.rotation(&camera),
+            camera,
+            palette: PalettePreset::Standard,
+            patterns: false,
+            background: Theme::default().clear_color(),
+        }
+    }
+
+    fn scenes() -> Vec<(&'static str, CaptureView)> {
+        let patterns = CaptureView {
+            palette: PalettePreset::Deuteranopia,
+            patterns: true,
+            ..scene(staircase(), ViewPreset::Isometric)
+        };
+        vec![
+            ("empty", scene(VoxelGrid::default(), ViewPreset::Isometric)),
+            ("isometric", scene(staircase(), ViewPreset::Isometric)),
+            ("front", scene(staircase(), ViewPreset::Front)),
+            ("top", scene(staircase(), ViewPreset::Top)),
+            ("patterns", patterns),
+        ]
+    }
+
+    /// CIELAB coordinates of an sRGB pixel under the D65 white point
+    fn lab(pixel: &image::Rgba<u8>) -> [f32; 3] {
+        let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(|channel| {
+            let c = channel as f32 / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        });
+        let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+        let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+        let [fx, fy, fz] = [x, y, z].map(|t| {
+            if t > 0.008856 {
+                t.cbrt()
+            } else {
+                7.787 * t + 16.0 / 116.0
+            }
+        });
+        [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+    }
+
+    /// Whether two pixels look different to a person
+    fn differs(expected: &image::Rgba<u8>, actual: &image::Rgba<u8>) -> bool {
+        let (a, b) = (lab(expected), lab(actual));
+        let distance = a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| (a - b).powi(2))
+            .sum::<f32>()
+            .sqrt();
+        distance > PIXEL_TOLERANCE
+    }
+
+    /// The expected image faded to gray with every differing pixel in red
+    fn diff_image(expected: &RgbaImage, actual: &RgbaImage) -> RgbaImage {
+        RgbaImage::from_fn(expected.width(), expected.height(), |x, y| {
+            let (expected, actual) = (expected.get_pixel(x, y), actual.get_pixel(x, y));
+            if differs(expected, actual) {
+                image::Rgba([255, 0, 0, 255])
+            } else {
+                let gray = (lab(expected)[0] / 100.0 * 255.0 / 3.0 + 170.0) as u8;
+                image::Rgba([gray, gray, gray, 255])
+            }
+        })
+    }
+
+    /// Compares `actual` with its golden. Returns why it doesn't match, after writing the actual and diff images next to the build output
+    fn compare(name: &str, actual: &RgbaImage) -> Result<(), String> {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let golden = root.join("tests/golden").join(format!("{name}.png"));
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            write_png(&golden, actual).map_err(|e| e.to_string())?;
+            return Ok(());
+        }
+        let expected = image::open(&golden)
+            .map_err(|e| {
+                format!(
+                    "{name}: couldn't open {}: {e}. Run with UPDATE_GOLDEN=1 to create it",
+                    golden.display()
+                )
+            })?
+            .to_rgba8();
+        let different = if expected.dimensions() == actual.dimensions() {
+            expected
+                .pixels()
+                .zip(actual.pixels())
+                .filter(|(expected, actual)| differs(expected, actual))
+                .count()
+        } else {
+            actual.pixels().len()
+        };
+        let share = different as f64 / actual.pixels().len() as f64;
+        if share <= MAX_DIFFERENT {
+            return Ok(());
+        }
+        let output = root.join("target/golden");
+        std::fs::create_dir_all(&output).map_err(|e| e.to_string())?;
+        let actual_path = output.join(format!("{name}-actual.png"));
+        write_png(&actual_path, actual).map_err(|e| e.to_string())?;
+        let mut message = format!(
+            "{name}: {:.2}% of pixels differ, see {}",
+            share * 100.0,
+            actual_path.display()
+        );
+        if expected.dimensions() == actual.dimensions() {
+            let diff_path = output.join(format!("{name}-diff.png"));
+            write_png(&diff_path, &diff_image(&expected, actual)).map_err(|e| e.to_string())?;
+            message += &format!(" and {}", diff_path.display());
+        }
+        Err(message)
+    }
+
+    #[test]
+    fn scenes_match_golden_images() {
+        // The software adapter renders the same everywhere, unlike whatever GPU the machine has
+        let args = Args {
+            fallback_adapter: true,
+            ..Args::default()
+        };
+        // Hosts without a software adapter can't render comparable images. They have to opt out explicitly, so a CI runner that lost its adapter doesn't pass without comparing anything
+        let headless = match Headless::new(&args) {
+            Ok(headless) => headless,
+            Err(e) if std::env::var_os("SKIP_GOLDEN").is_some() => {
+                eprintln!("Skipping golden image comparison because SKIP_GOLDEN is set: {e}");
+                return;
+            }
+            Err(e) => panic!("{e}. Set SKIP_GOLDEN=1 on hosts without a software adapter"),
+        };
+        let capture = Capture::new(&headless.device, &headless.layout, GOLDEN_SIZE, GOLDEN_SIZE);
+        let failures: Vec<String> = scenes()
+            .into_iter()
+            .filter_map(|(name, view)| {
+                let actual = capture
+                    .frame(&headless.device, &headless.queue, &view)
+                    .expect("capture");
+                compare(name, &actual).err()
+            })
+            .collect();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
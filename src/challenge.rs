@@ -0,0 +1,627 @@
+use std::{
+    f64::consts::FRAC_PI_2,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use clap::ValueEnum;
+use glam::{DQuat, Vec3};
+
+use crate::{
+    difficulty::DifficultyParams,
+    locale::Catalog,
+    voxel::{Voxel, VoxelGrid, GRID_SIZE},
+};
+
+/// Block colors used by generated challenges. Picked to stay distinct from each other and from the background
+pub const PALETTE: [Vec3; 6] = [
+    Vec3::new(0.86, 0.24, 0.22),
+    Vec3::new(0.95, 0.77, 0.2),
+    Vec3::new(0.3, 0.69, 0.31),
+    Vec3::new(0.55, 0.33, 0.75),
+    Vec3::new(0.96, 0.96, 0.96),
+    Vec3::new(0.2, 0.2, 0.2),
+];
+/// Catalog keys for the names of the [`PALETTE`] colors, used in instructions
+pub const COLOR_NAME_KEYS: [&str; 6] = [
+    "color-red",
+    "color-yellow",
+    "color-green",
+    "color-purple",
+    "color-white",
+    "color-black",
+];
+
+/// Where the puzzle for this session comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChallengeSource {
+    /// Generate the puzzle deterministically from a seed
+    Seed(u64),
+    /// Load a prebuilt puzzle from disk
+    File(PathBuf),
+}
+
+impl ChallengeSource {
+    /// A seed taken from the system clock. Good enough for local testing, the server should always hand out its own seed
+    pub fn random() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default();
+        ChallengeSource::Seed(nanos)
+    }
+}
+
+/// What the user is asked to do. Mixing kinds keeps a solver for any single puzzle from farming the captcha
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChallengeKind {
+    /// Build the target structure on an empty grid
+    Build,
+    /// Add the blocks missing from a partially built target
+    Complete,
+    /// Count the blocks of one color and type the number
+    Count,
+    /// Build the target flipped left to right
+    Mirror,
+    /// Remove the one block that breaks the structure's symmetry
+    OddOneOut,
+}
+
+impl ChallengeKind {
+    pub const ALL: [ChallengeKind; 5] = [
+        ChallengeKind::Build,
+        ChallengeKind::Complete,
+        ChallengeKind::Count,
+        ChallengeKind::Mirror,
+        ChallengeKind::OddOneOut,
+    ];
+
+    /// Whether the target structure is shown next to the grid. Count and odd one out puzzles are solved on the grid itself, showing a target would give them away
+    pub fn shows_target(self) -> bool {
+        matches!(
+            self,
+            ChallengeKind::Build | ChallengeKind::Complete | ChallengeKind::Mirror
+        )
+    }
+}
+
+/// Everything needed to produce the challenge shown to the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChallengeSpec {
+    pub source: ChallengeSource,
+    pub difficulty: DifficultyParams,
+    /// Kind of puzzle. Seeded challenges pick one from the seed when this is None, challenge files are always built as is
+    pub kind: Option<ChallengeKind>,
+}
+
+impl ChallengeSpec {
+    /// The same settings for a retry. Seeded challenges get a new seed so the user can't keep working on the one they failed, files can only be loaded again
+    pub fn fresh(&self) -> Self {
+        let source = match &self.source {
+            ChallengeSource::Seed(_) => ChallengeSource::random(),
+            ChallengeSource::File(path) => ChallengeSource::File(path.clone()),
+        };
+        Self {
+            source,
+            ..self.clone()
+        }
+    }
+}
+
+impl Default for ChallengeSpec {
+    fn default() -> Self {
+        Self {
+            source: ChallengeSource::random(),
+            difficulty: DifficultyParams::default(),
+            kind: None,
+        }
+    }
+}
+
+/// What counts as a correct answer
+#[derive(Debug, Clone, PartialEq)]
+pub enum Solution {
+    /// The grid has to end up holding this structure. It may be shifted anywhere on the floor
+    Grid(Box<VoxelGrid>),
+    /// The typed answer has to be this number
+    Count(usize),
+}
+
+/// A puzzle ready to be shown: what was asked for, what the user starts with and what solves it
+#[derive(Debug, Clone)]
+pub struct Challenge {
+    pub spec: ChallengeSpec,
+    pub kind: ChallengeKind,
+    /// Structure shown in the target preview
+    pub target: VoxelGrid,
+    /// What the user's grid holds when the challenge starts
+    pub start: VoxelGrid,
+    pub solution: Solution,
+    /// Palette index of the color count challenges ask about
+    counted_color: usize,
+    /// Quarter turns about the vertical axis the target preview is shown turned by
+    pub preview_turns: u32,
+}
+
+impl Challenge {
+    /// Generates or loads the challenge described by `spec`
+    pub fn new(spec: ChallengeSpec) -> Result<Self, ChallengeError> {
+        let seed = match &spec.source {
+            ChallengeSource::Seed(seed) => *seed,
+            ChallengeSource::File(path) => {
+                let target = load_target(path)?;
+                return Ok(Self {
+                    kind: ChallengeKind::Build,
+                    start: VoxelGrid::default(),
+                    solution: Solution::Grid(Box::new(target)),
+                    target,
+                    counted_color: 0,
+                    preview_turns: 0,
+                    spec,
+                });
+            }
+        };
+        let mut rng = Rng::new(seed);
+        // Always draw the kind so the structure for a seed doesn't change when the kind is forced
+        let drawn_kind = ChallengeKind::ALL[rng.below(ChallengeKind::ALL.len())];
+        let kind = spec.kind.unwrap_or(drawn_kind);
+        let difficulty = spec.difficulty;
+        let preview_turns = rng.below(difficulty.rotation_ambiguity as usize + 1) as u32;
+        let mut challenge = Self {
+            spec,
+            kind,
+            target: VoxelGrid::default(),
+            start: VoxelGrid::default(),
+            solution: Solution::Count(0),
+            counted_color: 0,
+            preview_turns,
+        };
+        match kind {
+            ChallengeKind::Build => {
+                challenge.target = generate_target(&mut rng, difficulty);
+                challenge.solution = Solution::Grid(Box::new(challenge.target));
+            }
+            ChallengeKind::Complete => {
+                let target = generate_target(&mut rng, difficulty);
+                // Leave out about a third of the blocks, always at least one. Shuffling first means no block is picked twice
+                let mut solid = solid_cells(&target);
+                rng.shuffle(&mut solid);
+                let missing = (solid.len() / 3).max(1);
+                challenge.start = target;
+                for &[x, y, z] in &solid[..missing] {
+                    challenge.start.set(x, y, z, Voxel::EMPTY);
+                }
+                challenge.target = target;
+                challenge.solution = Solution::Grid(Box::new(target));
+            }
+            ChallengeKind::Count => {
+                let structure = generate_target(&mut rng, difficulty);
+                let solid = solid_cells(&structure);
+                // Ask about a color that is actually present
+                let [x, y, z] = solid[rng.below(solid.len())];
+                let color = structure.get(x, y, z).color;
+                challenge.counted_color = PALETTE.iter().position(|&c| c == color).unwrap_or(0);
+                let count = solid
+                    .iter()
+                    .filter(|&&[x, y, z]| structure.get(x, y, z).color == color)
+                    .count();
+                challenge.start = structure;
+                challenge.solution = Solution::Count(count);
+            }
+            ChallengeKind::Mirror => {
+                // A target whose mirror is just a shifted or turned copy would make mirroring the same as copying, since `verify` accepts any placement or quarter turn, so draw again. Gives up after a few tries, which only happens for tiny structures
+                let mut target = generate_target(&mut rng, difficulty);
+                for _ in 0..16 {
+                    if shape(&mirrored(&target)) != shape(&target) {
+                        break;
+                    }
+                    target = generate_target(&mut rng, difficulty);
+                }
+                challenge.target = target;
+                challenge.solution = Solution::Grid(Box::new(mirrored(&target)));
+            }
+            ChallengeKind::OddOneOut => {
+                let symmetric = generate_symmetric(&mut rng, difficulty);
+                challenge.solution = Solution::Grid(Box::new(symmetric));
+                match symmetry_breaking_cell(&mut rng, &symmetric) {
+                    Some([x, y, z]) => {
+                        challenge.start = symmetric;
+                        let color = PALETTE[rng.below(difficulty.palette_size)];
+                        challenge.start.set(x, y, z, Voxel::solid(color));
+                    }
+                    // Only a structure filling the whole grid leaves no cell to break the symmetry with, so it is built from scratch instead
+                    None => {
+                        challenge.kind = ChallengeKind::Build;
+                        challenge.target = symmetric;
+                    }
+                }
+            }
+        }
+        Ok(challenge)
+    }
+
+    /// What the user has to do, shown at the top of the Captcha Controls window
+    pub fn instructions(&self, catalog: Catalog) -> String {
+        match self.kind {
+            ChallengeKind::Build => catalog.get("instructions-build").to_owned(),
+            ChallengeKind::Complete => catalog.get("instructions-complete").to_owned(),
+            ChallengeKind::Count => {
+                let color = catalog.get(COLOR_NAME_KEYS[self.counted_color]);
+                catalog.format("instructions-count", &[("color", &color)])
+            }
+            ChallengeKind::Mirror => catalog.get("instructions-mirror").to_owned(),
+            ChallengeKind::OddOneOut => catalog.get("instructions-odd-one-out").to_owned(),
+        }
+    }
+
+    /// Turn applied to the target in the preview on top of the main view's rotation
+    pub fn preview_offset(&self) -> DQuat {
+        DQuat::from_rotation_y(self.preview_turns as f64 * FRAC_PI_2)
+    }
+
+    /// Whether the puzzle is solved by this grid and typed answer. Grid solutions may sit anywhere on the floor and be turned in quarter turns as long as the shape and colors match
+    pub fn verify(&self, build: &VoxelGrid, answer: &str) -> bool {
+        match &self.solution {
+            Solution::Grid(solution) => shape(build) == shape(solution),
+            Solution::Count(count) => answer.trim().parse::<usize>() == Ok(*count),
+        }
+    }
+
+    /// Whether the rules allow another block in `build`. Builds may not use more blocks than the solution has, and count and odd one out puzzles only take answers or removals
+    pub fn allows_placement(&self, build: &VoxelGrid) -> bool {
+        match &self.solution {
+            Solution::Grid(_) if self.kind == ChallengeKind::OddOneOut => false,
+            Solution::Grid(solution) => build.solid_count() < solution.solid_count(),
+            Solution::Count(_) => false,
+        }
+    }
+
+    /// Whether blocks may be removed. Count puzzles would be trivial if the user could take the structure apart
+    pub fn allows_removal(&self) -> bool {
+        self.kind != ChallengeKind::Count
+    }
+
+    /// The structure the puzzle is about. Build puzzles show it as the target, count and odd one out puzzles put it in the grid and leave the target empty
+    pub fn structure(&self) -> &VoxelGrid {
+        if self.target.solid_count() > 0 {
+            &self.target
+        } else {
+            &self.start
+        }
+    }
+
+    /// Colors the user can build with. Generated challenges only use the first few palette colors, challenge files may use any of them
+    pub fn palette(&self) -> &'static [Vec3] {
+        match self.spec.source {
+            ChallengeSource::Seed(_) => {
+                &PALETTE[..self.spec.difficulty.palette_size.min(PALETTE.len())]
+            }
+            ChallengeSource::File(_) => &PALETTE,
+        }
+    }
+
+    /// Whether the answer is typed instead of built
+    pub fn needs_answer(&self) -> bool {
+        matches!(self.solution, Solution::Count(_))
+    }
+}
+
+/// Reasons a challenge file couldn't be used
+#[derive(Debug)]
+pub enum ChallengeError {
+    Io(PathBuf, io::Error),
+    /// A line that isn't `x y z r g b` with the cell inside the grid and the color from [`PALETTE`]
+    Parse {
+        path: PathBuf,
+        line: usize,
+    },
+}
+
+impl fmt::Display for ChallengeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChallengeError::Io(path, e) => {
+                write!(f, "Couldn't read challenge {}: {e}", path.display())
+            }
+            ChallengeError::Parse { path, line } => write!(
+                f,
+                "Challenge {} line {line} should be \"x y z r g b\" with x, y and z below {GRID_SIZE} and r, g and b one of the palette colors",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ChallengeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ChallengeError::Io(_, e) => Some(e),
+            ChallengeError::Parse { .. } => None,
+        }
+    }
+}
+
+/// SplitMix64. Tiny and stable across versions, which matters because the server has to regenerate the same puzzle from the seed to verify it
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `0..n`
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Puts `items` in a random order with a Fisher-Yates shuffle
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+/// Face neighbours of a cell
+const DIRECTIONS: [[i32; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
+/// How many times the generator may grow a structure to reach the requested occlusion depth
+const OCCLUSION_ATTEMPTS: usize = 32;
+
+/// Grows structures until one hides at least `occlusion_depth` blocks from the starting view. Keeps the most occluded one if none do
+fn generate_target(rng: &mut Rng, difficulty: DifficultyParams) -> VoxelGrid {
+    let mut best = grow_structure(rng, difficulty);
+    for _ in 1..OCCLUSION_ATTEMPTS {
+        if occluded_blocks(&best) >= difficulty.occlusion_depth {
+            break;
+        }
+        let candidate = grow_structure(rng, difficulty);
+        if occluded_blocks(&candidate) > occluded_blocks(&best) {
+            best = candidate;
+        }
+    }
+    best
+}
+
+/// Grows a connected structure from a cell on the floor, one face-adjacent block at a time
+fn grow_structure(rng: &mut Rng, difficulty: DifficultyParams) -> VoxelGrid {
+    let mut grid = VoxelGrid::default();
+    let palette = &PALETTE[..difficulty.palette_size];
+    let center = GRID_SIZE as i32 / 2;
+    // Start near the middle of the floor so the structure has room to grow in every direction
+    let mut cells = vec![[
+        center - 1 + rng.below(2) as i32,
+        0,
+        center - 1 + rng.below(2) as i32,
+    ]];
+    while cells.len() < difficulty.block_count {
+        let [x, y, z] = cells[rng.below(cells.len())];
+        let [dx, dy, dz] = DIRECTIONS[rng.below(DIRECTIONS.len())];
+        let cell = [x + dx, y + dy, z + dz];
+        if VoxelGrid::in_bounds(cell[0], cell[1], cell[2]) && !cells.contains(&cell) {
+            cells.push(cell);
+        }
+    }
+    for [x, y, z] in cells {
+        let color = palette[rng.below(palette.len())];
+        grid.set(x as usize, y as usize, z as usize, Voxel::solid(color));
+    }
+    grid
+}
+
+/// A structure that is its own mirror image. Half of it is grown against the mirror plane so both halves touch
+fn generate_symmetric(rng: &mut Rng, difficulty: DifficultyParams) -> VoxelGrid {
+    let half = GRID_SIZE / 2;
+    let palette = &PALETTE[..difficulty.palette_size];
+    let mut cells = vec![[half - 1, 0, half - 1 + rng.below(2)]];
+    while cells.len() < difficulty.block_count.div_ceil(2) {
+        let [x, y, z] = cells[rng.below(cells.len())].map(|v| v as i32);
+        let [dx, dy, dz] = DIRECTIONS[rng.below(DIRECTIONS.len())];
+        let cell = [x + dx, y + dy, z + dz];
+        // Stay on the low x side, the mirror fills in the other half
+        if VoxelGrid::in_bounds(cell[0], cell[1], cell[2]) && cell[0] < half as i32 {
+            let cell = cell.map(|v| v as usize);
+            if !cells.contains(&cell) {
+                cells.push(cell);
+            }
+        }
+    }
+    let mut grid = VoxelGrid::default();
+    for [x, y, z] in cells {
+        let voxel = Voxel::solid(palette[rng.below(palette.len())]);
+        grid.set(x, y, z, voxel);
+        grid.set(GRID_SIZE - 1 - x, y, z, voxel);
+    }
+    grid
+}
+
+/// An empty cell touching the structure whose mirror image is empty too, so filling it breaks the symmetry. None if there is no such cell, as in a full grid
+fn symmetry_breaking_cell(rng: &mut Rng, grid: &VoxelGrid) -> Option<[usize; 3]> {
+    let is_empty = |[x, y, z]: [i32; 3]| {
+        VoxelGrid::in_bounds(x, y, z) && !grid.get(x as usize, y as usize, z as usize).is_solid()
+    };
+    let mut candidates = Vec::new();
+    for [x, y, z] in solid_cells(grid)
+        .into_iter()
+        .map(|cell| cell.map(|v| v as i32))
+    {
+        for [dx, dy, dz] in DIRECTIONS {
+            let cell = [x + dx, y + dy, z + dz];
+            let mirror = [GRID_SIZE as i32 - 1 - cell[0], cell[1], cell[2]];
+            if is_empty(cell) && is_empty(mirror) && !candidates.contains(&cell) {
+                candidates.push(cell);
+            }
+        }
+    }
+    if candidates.is_empty() {
+        return None;
+    }
+    Some(candidates[rng.below(candidates.len())].map(|v| v as usize))
+}
+
+/// Every solid cell of the grid
+fn solid_cells(grid: &VoxelGrid) -> Vec<[usize; 3]> {
+    let mut cells = Vec::new();
+    for z in 0..GRID_SIZE {
+        for y in 0..GRID_SIZE {
+            for x in 0..GRID_SIZE {
+                if grid.get(x, y, z).is_solid() {
+                    cells.push([x, y, z]);
+                }
+            }
+        }
+    }
+    cells
+}
+
+/// The grid flipped left to right along the x axis
+fn mirrored(grid: &VoxelGrid) -> VoxelGrid {
+    let mut mirror = VoxelGrid::default();
+    for [x, y, z] in solid_cells(grid) {
+        mirror.set(GRID_SIZE - 1 - x, y, z, grid.get(x, y, z));
+    }
+    mirror
+}
+
+/// Solid blocks hidden behind another block when looking down the diagonal from the starting camera. The camera sits towards +x, +y and +z, so anything further along (1, 1, 1) is in front
+pub fn occluded_blocks(grid: &VoxelGrid) -> usize {
+    solid_cells(grid)
+        .into_iter()
+        .filter(|&cell| {
+            (1..GRID_SIZE)
+                .map(|k| cell.map(|v| v + k))
+                .take_while(|&[x, y, z]| x < GRID_SIZE && y < GRID_SIZE && z < GRID_SIZE)
+                .any(|[x, y, z]| grid.get(x, y, z).is_solid())
+        })
+        .count()
+}
+
+/// Solid cells and their colors in a canonical form: the quarter turn about the vertical axis that sorts first, moved so the structure starts at x = 0 and z = 0. Two grids with the same shape compare equal wherever they sit on the floor and however they are turned
+fn shape(grid: &VoxelGrid) -> Vec<([i32; 3], [u32; 3])> {
+    let blocks = solid_cells(grid)
+        .into_iter()
+        .map(|[x, y, z]| {
+            let color = grid.get(x, y, z).color.to_array().map(f32::to_bits);
+            ([x as i32, y as i32, z as i32], color)
+        })
+        .collect::<Vec<_>>();
+    (0..4)
+        .map(|turns| {
+            let mut turned = blocks
+                .iter()
+                .map(|&([x, y, z], color)| {
+                    let (x, z) = (0..turns).fold((x, z), |(x, z), _| (z, -x));
+                    ([x, y, z], color)
+                })
+                .collect::<Vec<_>>();
+            let min_x = turned.iter().map(|(cell, _)| cell[0]).min().unwrap_or(0);
+            let min_z = turned.iter().map(|(cell, _)| cell[2]).min().unwrap_or(0);
+            for (cell, _) in &mut turned {
+                cell[0] -= min_x;
+                cell[2] -= min_z;
+            }
+            turned.sort_unstable();
+            turned
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+/// Reads a challenge file with one solid voxel per line as `x y z r g b`. Blank lines and lines starting with # are skipped. Colors have to be one of the [`PALETTE`] entries
+fn load_target(path: &Path) -> Result<VoxelGrid, ChallengeError> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| ChallengeError::Io(path.to_path_buf(), e))?;
+    let mut grid = VoxelGrid::default();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parse_error = || ChallengeError::Parse {
+            path: path.to_path_buf(),
+            line: number + 1,
+        };
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        let [x, y, z, r, g, b] = fields[..] else {
+            return Err(parse_error());
+        };
+        let cell = [x, y, z].map(|value| value.parse::<usize>().ok().filter(|&v| v < GRID_SIZE));
+        let color = [r, g, b].map(|value| value.parse::<f32>().ok());
+        let ([Some(x), Some(y), Some(z)], [Some(r), Some(g), Some(b)]) = (cell, color) else {
+            return Err(parse_error());
+        };
+        // The color picker only offers the palette, so any other color could never be built
+        let color = Vec3::new(r, g, b);
+        if !PALETTE.contains(&color) {
+            return Err(parse_error());
+        }
+        grid.set(x, y, z, Voxel::solid(color));
+    }
+    Ok(grid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::difficulty::Difficulty;
+
+    fn seeded(seed: u64, kind: ChallengeKind, difficulty: Difficulty) -> Challenge {
+        Challenge::new(ChallengeSpec {
+            source: ChallengeSource::Seed(seed),
+            difficulty: difficulty.params(),
+            kind: Some(kind),
+        })
+        .expect("seeded challenges always generate")
+    }
+
+    #[test]
+    fn complete_leaves_out_distinct_blocks() {
+        for difficulty in [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard] {
+            for seed in 0..64 {
+                let challenge = seeded(seed, ChallengeKind::Complete, difficulty);
+                let total = challenge.target.solid_count();
+                let missing = (total / 3).max(1);
+                assert_eq!(
+                    challenge.start.solid_count(),
+                    total - missing,
+                    "seed {seed}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn full_grid_has_no_symmetry_breaking_cell() {
+        let mut grid = VoxelGrid::default();
+        for z in 0..GRID_SIZE {
+            for y in 0..GRID_SIZE {
+                for x in 0..GRID_SIZE {
+                    grid.set(x, y, z, Voxel::solid(PALETTE[0]));
+                }
+            }
+        }
+        assert_eq!(symmetry_breaking_cell(&mut Rng::new(0), &grid), None);
+
+        // Emptying a cell and its mirror opens both up again
+        grid.set(0, GRID_SIZE - 1, 0, Voxel::EMPTY);
+        grid.set(GRID_SIZE - 1, GRID_SIZE - 1, 0, Voxel::EMPTY);
+        assert!(symmetry_breaking_cell(&mut Rng::new(0), &grid).is_some());
+    }
+}
@@ -0,0 +1,286 @@
+use std::{path::PathBuf, time::Duration};
+
+use clap::{Parser, ValueEnum};
+use wgpu::{Backends, PowerPreference, PresentMode, RequestAdapterOptions, Surface};
+use winit::{
+    dpi::PhysicalSize,
+    window::{Fullscreen, Window, WindowAttributes},
+};
+
+use crate::{
+    challenge::{ChallengeKind, ChallengeSource, ChallengeSpec},
+    difficulty::{Difficulty, DifficultyParams},
+    palette::PalettePreset,
+};
+
+/// Command line arguments for the MineCaptcha client
+#[derive(Parser, Debug, Clone, Default)]
+#[command(version, about = "Voxel based CAPTCHA client")]
+pub struct Args {
+    /// Seed used to generate the challenge. A random seed is used if neither this nor --challenge is given
+    #[arg(long, conflicts_with = "challenge")]
+    pub seed: Option<u64>,
+
+    /// Load the challenge from a file instead of generating it from a seed
+    #[arg(long, value_name = "FILE")]
+    pub challenge: Option<PathBuf>,
+
+    /// Difficulty of the generated challenge
+    #[arg(long, value_enum, default_value_t = Difficulty::default())]
+    pub difficulty: Difficulty,
+
+    /// Risk score from the server between 0 (human) and 1 (bot). Scales the difficulty instead of using a preset
+    #[arg(long, value_name = "SCORE", conflicts_with = "difficulty")]
+    pub risk: Option<f64>,
+
+    /// Kind of puzzle to generate. Picked from the seed if not given. Challenge files are always build puzzles
+    #[arg(long, value_enum, conflicts_with = "challenge")]
+    pub kind: Option<ChallengeKind>,
+
+    /// Initial window width in physical pixels
+    #[arg(long, requires = "height")]
+    pub width: Option<u32>,
+
+    /// Initial window height in physical pixels
+    #[arg(long, requires = "width")]
+    pub height: Option<u32>,
+
+    /// Start in borderless fullscreen on the current monitor
+    #[arg(long)]
+    pub fullscreen: bool,
+
+    /// Graphics backend WGPU is allowed to use. Falls back to the WGPU_BACKEND environment variable, then to every backend
+    #[arg(long, value_enum)]
+    pub backend: Option<Backend>,
+
+    /// GPU power preference. Falls back to the WGPU_POWER_PREF environment variable
+    #[arg(long, value_enum)]
+    pub power_preference: Option<PowerPref>,
+
+    /// Pick the first adapter whose name contains this text. Falls back to the WGPU_ADAPTER_NAME environment variable
+    #[arg(long, value_name = "NAME")]
+    pub adapter_name: Option<String>,
+
+    /// Force the use of a fallback (software) adapter
+    #[arg(long)]
+    pub fallback_adapter: bool,
+
+    /// Size of the controls on top of the display's scale factor, between 0.5 and 3. Can also be changed in the controls window
+    #[arg(long, value_name = "FACTOR", default_value_t = 1.0)]
+    pub ui_scale: f32,
+
+    /// Limit rendering to this many frames per second. Frames are only drawn when something changed either way
+    #[arg(long, value_name = "FPS")]
+    pub fps_cap: Option<u32>,
+
+    /// How frames are presented. Unsupported modes fall back to fifo, which every platform supports
+    #[arg(long, value_enum, default_value_t = VsyncMode::default())]
+    pub vsync: VsyncMode,
+
+    /// Render the challenge's structure to this PNG without showing a window, then exit. Uses --width and --height if given. F12 saves the current view while the window is open
+    #[arg(long, value_name = "FILE")]
+    pub screenshot: Option<PathBuf>,
+
+    /// Render the challenge's structure making a full turn to this animated GIF without showing a window, then exit. Shift+F12 saves one of the current view while the window is open
+    #[arg(long, value_name = "FILE")]
+    pub turntable: Option<PathBuf>,
+
+    /// Frames in a turntable GIF
+    #[arg(long, value_name = "COUNT", default_value_t = 36, value_parser = clap::value_parser!(u32).range(1..))]
+    pub turntable_frames: u32,
+
+    /// Record mouse and keyboard input to this file while the window is open, for playing back with --replay
+    #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Play back input recorded with --record instead of taking it from the user. Positions are in pixels, so use the same --width and --height as the recording
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+
+    /// How many submissions and expiries the user gets before the challenge counts as failed
+    #[arg(long, value_name = "COUNT", default_value_t = 3)]
+    pub max_attempts: u32,
+
+    /// Print challenge state transitions to stdout as JSON lines, for hosts driving the client as a subprocess
+    #[arg(long)]
+    pub report_state: bool,
+
+    /// Generate this many challenges of each kind with the chosen difficulty, print their average metrics and exit without opening a window
+    #[arg(long, value_name = "COUNT", conflicts_with = "challenge")]
+    pub calibrate: Option<usize>,
+
+    /// Start with the text version of the challenge, which describes the structure and asks questions for screen reader users
+    #[arg(long)]
+    pub text_mode: bool,
+
+    /// Colors blocks are drawn with. The presets for color blindness can also be picked in the controls window
+    #[arg(long, value_enum, default_value_t = PalettePreset::default())]
+    pub palette: PalettePreset,
+
+    /// Draw a different pattern on each block color, so colors can be told apart without seeing hue
+    #[arg(long)]
+    pub patterns: bool,
+
+    /// Language of the window, such as `de` or `es_MX`. Falls back to the LC_ALL, LC_MESSAGES and LANG environment variables, then to English
+    #[arg(long, value_name = "LOCALE")]
+    pub locale: Option<String>,
+
+    /// Theme file setting the style of the controls, the background color, the font and a logo
+    #[arg(long, value_name = "FILE")]
+    pub theme: Option<PathBuf>,
+}
+
+/// Graphics backends selectable from the command line
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// Let WGPU pick from every backend available on the platform
+    #[default]
+    All,
+    Vulkan,
+    Gl,
+    Metal,
+    Dx12,
+}
+
+/// GPU power preferences selectable from the command line
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerPref {
+    /// No preference, let the driver decide
+    None,
+    /// Prefer integrated or otherwise low power GPUs
+    Low,
+    /// Prefer discrete or otherwise high performance GPUs
+    High,
+}
+
+impl From<PowerPref> for PowerPreference {
+    fn from(value: PowerPref) -> Self {
+        match value {
+            PowerPref::None => PowerPreference::None,
+            PowerPref::Low => PowerPreference::LowPower,
+            PowerPref::High => PowerPreference::HighPerformance,
+        }
+    }
+}
+
+/// Presentation modes selectable from the command line. See [`PresentMode`] for the details of each
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VsyncMode {
+    /// Vsync on, using fifo-relaxed or fifo
+    #[default]
+    Auto,
+    /// Vsync off, using immediate or mailbox if available
+    Off,
+    Fifo,
+    FifoRelaxed,
+    Mailbox,
+    Immediate,
+}
+
+impl From<VsyncMode> for PresentMode {
+    fn from(value: VsyncMode) -> Self {
+        match value {
+            VsyncMode::Auto => PresentMode::AutoVsync,
+            VsyncMode::Off => PresentMode::AutoNoVsync,
+            VsyncMode::Fifo => PresentMode::Fifo,
+            VsyncMode::FifoRelaxed => PresentMode::FifoRelaxed,
+            VsyncMode::Mailbox => PresentMode::Mailbox,
+            VsyncMode::Immediate => PresentMode::Immediate,
+        }
+    }
+}
+
+impl Backend {
+    /// The WGPU backend bits used for the `InstanceDescriptor`
+    pub fn backends(self) -> Backends {
+        match self {
+            Backend::All => Backends::all(),
+            Backend::Vulkan => Backends::VULKAN,
+            Backend::Gl => Backends::GL,
+            Backend::Metal => Backends::METAL,
+            Backend::Dx12 => Backends::DX12,
+        }
+    }
+}
+
+impl Args {
+    /// Backends to create the WGPU instance with. The command line wins over the environment
+    pub fn backends(&self) -> Backends {
+        match self.backend {
+            Some(backend) => backend.backends(),
+            None => wgpu::util::backend_bits_from_env().unwrap_or(Backends::all()),
+        }
+    }
+
+    /// Power preference for adapter selection. The command line wins over the environment
+    pub fn power_preference(&self) -> PowerPreference {
+        match self.power_preference {
+            Some(preference) => preference.into(),
+            None => wgpu::util::power_preference_from_env().unwrap_or_default(),
+        }
+    }
+
+    /// Requested adapter name, lowercased for matching
+    pub fn adapter_name(&self) -> Option<String> {
+        self.adapter_name
+            .clone()
+            .or_else(|| std::env::var("WGPU_ADAPTER_NAME").ok())
+            .map(|name| name.to_lowercase())
+    }
+
+    /// Locale to pick translations for. The command line wins over the environment, which is checked in the same order as gettext does
+    pub fn locale(&self) -> Option<String> {
+        self.locale.clone().or_else(|| {
+            ["LC_ALL", "LC_MESSAGES", "LANG"]
+                .iter()
+                .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+        })
+    }
+
+    /// Options used when requesting an adapter for the given surface
+    pub fn adapter_options<'a, 'b>(
+        &self,
+        surface: Option<&'a Surface<'b>>,
+    ) -> RequestAdapterOptions<'a, 'b> {
+        RequestAdapterOptions {
+            power_preference: self.power_preference(),
+            compatible_surface: surface,
+            force_fallback_adapter: self.fallback_adapter,
+        }
+    }
+
+    /// Shortest time between two frames allowed by the FPS cap
+    pub fn min_frame_time(&self) -> Option<Duration> {
+        self.fps_cap
+            .filter(|&fps| fps > 0)
+            .map(|fps| Duration::from_secs_f64(1.0 / fps as f64))
+    }
+
+    /// Window attributes built from the size and fullscreen flags
+    pub fn window_attributes(&self, title: &str) -> WindowAttributes {
+        let mut attributes = Window::default_attributes().with_title(title);
+        if let (Some(width), Some(height)) = (self.width, self.height) {
+            attributes = attributes.with_inner_size(PhysicalSize::new(width, height));
+        }
+        if self.fullscreen {
+            attributes = attributes.with_fullscreen(Some(Fullscreen::Borderless(None)));
+        }
+        attributes
+    }
+
+    /// The challenge the user asked for
+    pub fn challenge_spec(&self) -> ChallengeSpec {
+        let source = match (&self.challenge, self.seed) {
+            (Some(path), _) => ChallengeSource::File(path.clone()),
+            (None, Some(seed)) => ChallengeSource::Seed(seed),
+            (None, None) => ChallengeSource::random(),
+        };
+        ChallengeSpec {
+            source,
+            difficulty: self
+                .risk
+                .map_or_else(|| self.difficulty.params(), DifficultyParams::from_risk),
+            kind: self.kind,
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use clap::ValueEnum;
+
+use crate::challenge::PALETTE;
+
+/// Difficulty presets selectable from the command line. Each maps to a set of [`DifficultyParams`]
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn params(self) -> DifficultyParams {
+        match self {
+            Difficulty::Easy => DifficultyParams {
+                block_count: 4,
+                palette_size: 2,
+                occlusion_depth: 0,
+                rotation_ambiguity: 0,
+                time_limit: Some(Duration::from_secs(120)),
+            },
+            Difficulty::Normal => DifficultyParams {
+                block_count: 7,
+                palette_size: 3,
+                occlusion_depth: 1,
+                rotation_ambiguity: 0,
+                time_limit: Some(Duration::from_secs(90)),
+            },
+            Difficulty::Hard => DifficultyParams {
+                block_count: 12,
+                palette_size: PALETTE.len(),
+                occlusion_depth: 2,
+                rotation_ambiguity: 1,
+                time_limit: Some(Duration::from_secs(60)),
+            },
+        }
+    }
+}
+
+/// The knobs that make a generated challenge harder. Presets come from [`Difficulty`], the server can also derive them from a risk score with [`DifficultyParams::from_risk`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DifficultyParams {
+    /// Number of blocks in the generated structure
+    pub block_count: usize,
+    /// Number of palette colors the structure may use
+    pub palette_size: usize,
+    /// Blocks the generator tries to hide behind others from the starting view. See [`crate::challenge::occluded_blocks`]
+    pub occlusion_depth: usize,
+    /// Up to how many quarter turns the target preview may be turned away from the user's grid. Verification accepts quarter turned builds either way
+    pub rotation_ambiguity: u32,
+    /// How long the user has to solve the challenge. None means no limit
+    pub time_limit: Option<Duration>,
+}
+
+impl Default for DifficultyParams {
+    fn default() -> Self {
+        Difficulty::default().params()
+    }
+}
+
+impl DifficultyParams {
+    /// Parameters for a client with the given risk score, 0 for clearly human and 1 for almost certainly a bot. Riskier clients get bigger, more colorful and more occluded structures with less time to solve them
+    pub fn from_risk(risk: f64) -> Self {
+        let risk = if risk.is_finite() {
+            risk.clamp(0.0, 1.0)
+        } else {
+            // A broken score is treated as the most suspicious one
+            1.0
+        };
+        let lerp = |low: f64, high: f64| (low + (high - low) * risk).round();
+        Self {
+            block_count: lerp(4.0, 16.0) as usize,
+            palette_size: lerp(2.0, PALETTE.len() as f64) as usize,
+            occlusion_depth: lerp(0.0, 4.0) as usize,
+            rotation_ambiguity: lerp(0.0, 3.0) as u32,
+            time_limit: Some(Duration::from_secs(lerp(120.0, 45.0) as u64)),
+        }
+    }
+}
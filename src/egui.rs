@@ -1,173 +1,333 @@
-use std::f64::consts::PI;
-
-use egui::{Align2, Color32, Context, Frame, Id, Pos2, Rect, Sense, Vec2};
-use nalgebra::{Matrix4, Point3, Vector3 as NVec3};
-use transform_gizmo_egui::{
-    enum_set,
-    math::Transform,
-    mint::{Quaternion, RowMatrix4, Vector3, Vector4},
-    Gizmo, GizmoConfig, GizmoExt, GizmoMode, GizmoVisuals,
+use std::{f64::consts::FRAC_PI_2, ops::RangeInclusive, time::Instant};
+
+use egui::{Align2, Color32, Context, Sense, Ui, Vec2, WidgetInfo, WidgetType};
+use transform_gizmo_egui::{enum_set, GizmoConfig, GizmoExt, GizmoMode};
+
+use crate::{
+    camera::{from_gizmo_rotation, to_gizmo_transform, ViewPreset},
+    challenge::{COLOR_NAME_KEYS, PALETTE},
+    egui_render::AppState,
+    palette::PalettePreset,
+    session::SessionState,
+    voxel::VoxelGrid,
 };
 
-use crate::egui_render::AppState;
+/// Smallest and largest side of the rotation gizmo in points. In between it follows the window size
+const GIZMO_SIDE: RangeInclusive<f32> = 120.0..=260.0;
+/// Smallest and largest side of the target preview in points
+const PREVIEW_SIDE: RangeInclusive<f32> = 120.0..=240.0;
+/// Side of a block color swatch in points
+const SWATCH_SIDE: f32 = 24.0;
+/// Tallest the theme's logo is drawn, in points
+const LOGO_HEIGHT: f32 = 48.0;
+/// Zoom factors the UI size setting allows, on top of the display's scale factor
+pub const UI_SCALE: RangeInclusive<f32> = 0.5..=3.0;
+/// How much one press of the UI size buttons changes the zoom
+const UI_SCALE_STEP: f32 = 0.1;
 
-const gizmo_legth_side: f32 = 220.0;
+/// Side of a square widget that takes up `fraction` of the smaller window dimension, kept inside `range`. Screen sizes are in points, so this already accounts for the scale factor and zoom
+fn responsive_side(ui: &Context, fraction: f32, range: RangeInclusive<f32>) -> f32 {
+    let screen = ui.screen_rect().size();
+    (screen.min_elem() * fraction).clamp(*range.start(), *range.end())
+}
 
 /// This is the function that the egui renderer renders. This is what's most applicable in a cross application format
 pub fn gui(ui: &Context, app_state: &mut AppState) {
-    let mut window_pos = Pos2::ZERO;
-    let mut window_size = Vec2::ZERO;
-    egui::Window::new("Captcha Controls")
-        // .vscroll(true)
+    let catalog = app_state.catalog;
+    egui::Window::new(catalog.get("window-controls"))
         .default_open(true)
-        .default_width(800.0)
-        .default_height(1000.0)
-        .resizable(false)
+        .default_width(320.0)
+        .resizable(true)
         .movable(true)
+        // Scrolls instead of running off screen when every section is open on a small window
+        .vscroll(true)
         .show(ui, |ui| {
-            ui.label("Controls:");
-            ui.label("\tScroll Wheel: Zoom In and Out");
-            ui.label("\tLeft Mouse Button Click: Place Block");
-            ui.label("\tRight Mouse Button Click: Remove Block");
-            ui.label("\tUse Gimbal for Rotation");
-
-            // Store the window's position and size
-            window_pos = ui.min_rect().min;
-            window_size = ui.min_rect().size();
-
-            // Calculate gizmo size and position
-            let gizmo_size = Vec2::new(gizmo_legth_side, gizmo_legth_side);
-            let gizmo_pos = window_pos + Vec2::new(10.0, 90.0); // Adjust these offsets as needed
-
-            let mut transform = Transform::from_scale_rotation_translation(
-                Vector3 {
-                    x: 1.0,
-                    y: 1.0,
-                    z: 1.0,
-                },
-                app_state.rotation,
-                Vector3 {
-                    x: 0.0,
-                    y: 0.0,
-                    z: 0.0,
-                },
-            );
-
-            // Create a non-interactable sub-area for the gizmo
-            // Create a non-draggable area for the gizmo
-            let gizmo_rect = Rect::from_min_size(gizmo_pos, gizmo_size);
-
-            let gizmo_response = ui.allocate_rect(gizmo_rect, Sense::click_and_drag());
-
-            if gizmo_response.clicked() || gizmo_response.dragged() {}
-
-            let gizmo_rect = gizmo_response.rect;
-
-            // Draw the background and border
-            ui.painter()
-                .rect_filled(gizmo_rect, 0.0, Color32::from_black_alpha(10));
-            ui.painter()
-                .rect_stroke(gizmo_rect, 0.0, (1.0, Color32::WHITE));
+            if let Some(logo) = &app_state.logo {
+                ui.add(
+                    egui::Image::new(logo)
+                        .max_height(LOGO_HEIGHT)
+                        .max_width(ui.available_width()),
+                );
+            }
+            // Sections are laid out top to bottom, so each one pushes the next down however tall its text ends up
+            egui::CollapsingHeader::new(catalog.get("section-help"))
+                .default_open(true)
+                .show(ui, |ui| help(ui, app_state));
+            egui::CollapsingHeader::new(catalog.get("section-view"))
+                .default_open(true)
+                .show(ui, |ui| view_controls(ui, app_state));
+            egui::CollapsingHeader::new(catalog.get("section-display"))
+                .default_open(false)
+                .show(ui, |ui| display_settings(ui, app_state));
+            // Puzzles that never take a new block have no use for a color
+            if app_state.challenge.allows_placement(&VoxelGrid::default()) {
+                egui::CollapsingHeader::new(catalog.get("section-blocks"))
+                    .default_open(true)
+                    .show(ui, |ui| block_palette(ui, app_state));
+            }
+            egui::CollapsingHeader::new(catalog.get("section-challenge"))
+                .default_open(true)
+                .show(ui, |ui| challenge_status(ui, app_state));
+        });
+    target_preview(ui, app_state);
+}
 
-            let (view_matrix, projection_matrix) = make_matrices();
+/// Mouse and keyboard bindings
+fn help(ui: &mut Ui, app_state: &AppState) {
+    for key in [
+        "controls-zoom",
+        "controls-place",
+        "controls-remove",
+        "controls-rotate",
+        "controls-pan",
+        "controls-reset",
+        "controls-cursor",
+        "controls-keyboard-edit",
+        "controls-turn",
+        "controls-capture",
+    ] {
+        ui.label(app_state.catalog.get(key));
+    }
+}
 
-            let gizmo_config = GizmoConfig {
-                view_matrix,
-                projection_matrix,
-                viewport: gizmo_rect,
-                modes: enum_set!(GizmoMode::RotateX | GizmoMode::RotateY | GizmoMode::RotateZ),
-                ..Default::default()
+/// The rotation gizmo and the view presets
+fn view_controls(ui: &mut Ui, app_state: &mut AppState) {
+    let catalog = app_state.catalog;
+    // Square, following the window size but never wider than the panel
+    let gizmo_side = responsive_side(ui.ctx(), 0.3, GIZMO_SIDE).min(ui.available_width());
+    let (gizmo_rect, gizmo_response) =
+        ui.allocate_exact_size(Vec2::splat(gizmo_side), Sense::click_and_drag());
+    // Same as the R key. The gizmo's rings take drags, so double clicking is what reaches this response
+    if gizmo_response.double_clicked() {
+        app_state.camera.reset_view();
+    }
+    gizmo_response.on_hover_text(catalog.get("gizmo-hint"));
+
+    // Draw the background and border
+    ui.painter()
+        .rect_filled(gizmo_rect, 0.0, Color32::from_black_alpha(10));
+    ui.painter()
+        .rect_stroke(gizmo_rect, 0.0, (1.0, Color32::WHITE));
+
+    let mut transform = to_gizmo_transform(app_state.rotation);
+    let (view_matrix, projection_matrix) = app_state
+        .camera
+        .gizmo_matrices(gizmo_rect.aspect_ratio() as f64);
+    let gizmo_config = GizmoConfig {
+        view_matrix,
+        projection_matrix,
+        viewport: gizmo_rect,
+        modes: enum_set!(GizmoMode::RotateX | GizmoMode::RotateY | GizmoMode::RotateZ),
+        snapping: app_state.snap_rotation,
+        snap_angle: FRAC_PI_2 as f32,
+        ..Default::default()
+    };
+    app_state.gizmo.update_config(gizmo_config);
+
+    if let Some((_, new_transforms)) = app_state.gizmo.interact(ui, &[transform]) {
+        for (new_transform, transform) in new_transforms.iter().zip(std::iter::once(&mut transform))
+        {
+            *transform = *new_transform;
+        }
+    }
+    let rotation = from_gizmo_rotation(transform.rotation);
+    if app_state.rotation != rotation {
+        app_state.set_rotation(rotation);
+    }
+
+    ui.label(catalog.get("views-heading"));
+    ui.horizontal_wrapped(|ui| {
+        for preset in ViewPreset::ALL {
+            if ui.button(catalog.get(preset.label_key())).clicked() {
+                let target = preset.rotation(&app_state.camera);
+                app_state.animate_rotation(target);
+            }
+        }
+    });
+    ui.checkbox(&mut app_state.snap_rotation, catalog.get("snap-rotation"));
+}
+
+/// Color presets, patterns and UI size
+fn display_settings(ui: &mut Ui, app_state: &mut AppState) {
+    let catalog = app_state.catalog;
+    ui.horizontal(|ui| {
+        ui.label(catalog.get("colors-heading"));
+        egui::ComboBox::from_id_salt("palette")
+            .selected_text(catalog.get(app_state.palette.label_key()))
+            .show_ui(ui, |ui| {
+                for preset in PalettePreset::ALL {
+                    let label = catalog.get(preset.label_key());
+                    ui.selectable_value(&mut app_state.palette, preset, label);
+                }
+            });
+    });
+    ui.checkbox(&mut app_state.patterns, catalog.get("patterns"));
+    ui_scale(ui, app_state);
+}
+
+/// Swatches for the color of the next placed block, drawn the way the current preset draws blocks
+fn block_palette(ui: &mut Ui, app_state: &mut AppState) {
+    let catalog = app_state.catalog;
+    let shown = app_state.palette.colors();
+    ui.horizontal_wrapped(|ui| {
+        for (index, &color) in app_state.challenge.palette().iter().enumerate() {
+            let name = catalog.get(COLOR_NAME_KEYS[index]);
+            let selected = app_state.selected_color == color;
+            let (rect, response) = ui.allocate_exact_size(Vec2::splat(SWATCH_SIDE), Sense::click());
+            // Screen readers announce swatches as radio buttons named after their color
+            response.widget_info(|| {
+                WidgetInfo::selected(WidgetType::RadioButton, true, selected, name)
+            });
+            let [r, g, b] = shown[index]
+                .to_array()
+                .map(|channel| (channel * 255.0) as u8);
+            ui.painter()
+                .rect_filled(rect, 2.0, Color32::from_rgb(r, g, b));
+            let stroke = if selected {
+                (3.0, ui.visuals().selection.stroke.color)
+            } else {
+                (1.0, ui.visuals().widgets.inactive.fg_stroke.color)
             };
-            app_state.gizmo.update_config(gizmo_config);
+            ui.painter().rect_stroke(rect, 2.0, stroke);
+            if response.on_hover_text(name).clicked() {
+                app_state.selected_color = color;
+            }
+        }
+    });
+    // Named as well, so the choice doesn't rest on telling the swatches apart
+    if let Some(index) = PALETTE
+        .iter()
+        .position(|&color| color == app_state.selected_color)
+    {
+        let selected = catalog.get(COLOR_NAME_KEYS[index]);
+        ui.label(catalog.format("selected-color", &[("color", &selected)]));
+    }
+}
+
+/// Buttons for the user's zoom on top of the display's scale factor. They edit egui's zoom directly, which also follows Ctrl + and Ctrl -
+fn ui_scale(ui: &mut Ui, app_state: &AppState) {
+    let catalog = app_state.catalog;
+    let zoom = ui.ctx().zoom_factor();
+    ui.horizontal(|ui| {
+        ui.label(catalog.get("ui-scale"));
+        let mut target = zoom;
+        if ui.button(catalog.get("ui-scale-smaller")).clicked() {
+            target -= UI_SCALE_STEP;
+        }
+        ui.label(catalog.format("ui-scale-percent", &[("percent", &(zoom * 100.0).round())]));
+        if ui.button(catalog.get("ui-scale-larger")).clicked() {
+            target += UI_SCALE_STEP;
+        }
+        if ui.button(catalog.get("ui-scale-reset")).clicked() {
+            target = 1.0;
+        }
+        let target = target.clamp(*UI_SCALE.start(), *UI_SCALE.end());
+        if target != zoom {
+            ui.ctx().set_zoom_factor(target);
+        }
+    });
+}
 
-            if let Some((result, new_transforms)) = app_state.gizmo.interact(&ui, &[transform]) {
-                for (new_transform, transform) in
-                    new_transforms.iter().zip(std::iter::once(&mut transform))
+/// Instructions, countdown and the submit or retry controls, depending on where the session is
+fn challenge_status(ui: &mut Ui, app_state: &mut AppState) {
+    let catalog = app_state.catalog;
+    match app_state.session.state() {
+        SessionState::Loading => {
+            ui.label(catalog.get("status-loading"));
+        }
+        SessionState::Submitted => {
+            ui.label(catalog.get("status-checking"));
+        }
+        SessionState::Active => {
+            ui.checkbox(&mut app_state.text_mode, catalog.get("text-mode"));
+            let instructions = if app_state.text_mode {
+                text_challenge(ui, app_state)
+            } else {
+                ui.label(app_state.challenge.instructions(catalog))
+            };
+            if let Some(remaining) = app_state.session.remaining(Instant::now()) {
+                let seconds = remaining.as_secs_f64().ceil();
+                ui.label(catalog.format("time-left", &[("seconds", &seconds)]));
+            }
+            if !app_state.text_mode && app_state.challenge.needs_answer() {
+                ui.text_edit_singleline(&mut app_state.answer)
+                    .labelled_by(instructions.id);
+            }
+            if ui.button(catalog.get("submit")).clicked() {
+                app_state.submit();
+            }
+        }
+        SessionState::Passed => {
+            ui.colored_label(Color32::GREEN, catalog.get("status-passed"));
+        }
+        state @ (SessionState::Failed | SessionState::Expired) => {
+            let message = if state == SessionState::Expired {
+                catalog.get("status-expired")
+            } else {
+                catalog.get("status-failed")
+            };
+            ui.colored_label(Color32::RED, message);
+            if app_state.session.can_retry() {
+                let attempts_left = app_state.session.attempts_left();
+                if ui
+                    .button(catalog.format("retry", &[("attempts", &attempts_left)]))
+                    .clicked()
+                    && let Err(e) = app_state.retry()
                 {
-                    *transform = *new_transform;
+                    log::error!("{e}");
                 }
+            } else {
+                ui.label(catalog.get("no-attempts"));
             }
-            if app_state.rotation != transform.rotation {
-                app_state.rotation = transform.rotation;
-            }
-        });
+        }
+    }
 }
 
-// This recalculates every frame. Fix it later
-fn make_matrices() -> (RowMatrix4<f64>, RowMatrix4<f64>) {
-    // Define the camera position, looking down the diagonal at 45-degree angles
-    let aspect_ratio = 1.;
-    let fov = std::f64::consts::FRAC_PI_4;
-    // Clipping planes. Cannot be the same and near must be above 0
-    let near = 0.1;
-    let far = 1.;
-    let mut eye = Point3::new(1.0, 1.0, 1.0);
-    let eye_vec = NVec3::new(eye.x, eye.y, eye.z).normalize(); // Normalize the vector
-    eye = Point3::new(eye_vec.x, eye_vec.y, eye_vec.z); // Convert back to Point3
-    let target = Point3::new(0.0, 0.0, 0.0); // Looking at the origin
-    let up = NVec3::new(0.0, 1.0, 0.0); // World "up" direction
-
-    // Create the view matrix using nalgebra's look_at function
-    let view_matrix: Matrix4<f64> = Matrix4::look_at_rh(&eye, &target, &up);
-
-    // Create the perspective projection matrix using field of view and aspect ratio
-    let projection_matrix: Matrix4<f64> = Matrix4::new_perspective(aspect_ratio, fov, near, far);
-
-    // Convert the view matrix to RowMatrix4
-    let view_matrix = RowMatrix4 {
-        x: Vector4 {
-            x: view_matrix[(0, 0)],
-            y: view_matrix[(0, 1)],
-            z: view_matrix[(0, 2)],
-            w: view_matrix[(0, 3)],
-        },
-        y: Vector4 {
-            x: view_matrix[(1, 0)],
-            y: view_matrix[(1, 1)],
-            z: view_matrix[(1, 2)],
-            w: view_matrix[(1, 3)],
-        },
-        z: Vector4 {
-            x: view_matrix[(2, 0)],
-            y: view_matrix[(2, 1)],
-            z: view_matrix[(2, 2)],
-            w: view_matrix[(2, 3)],
-        },
-        w: Vector4 {
-            x: view_matrix[(3, 0)],
-            y: view_matrix[(3, 1)],
-            z: view_matrix[(3, 2)],
-            w: view_matrix[(3, 3)],
-        },
-    };
+/// The structure read out layer by layer with a text field per question. Each field is labelled by its question so screen readers announce them together. Returns the heading
+fn text_challenge(ui: &mut Ui, app_state: &mut AppState) -> egui::Response {
+    let heading = ui.label(app_state.catalog.get("text-intro"));
+    for layer in &app_state.text_challenge.layers {
+        ui.label(layer);
+    }
+    ui.label(app_state.catalog.get("text-answer-hint"));
+    for (question, answer) in app_state
+        .text_challenge
+        .questions
+        .iter()
+        .zip(&mut app_state.text_answers)
+    {
+        let prompt = ui.label(&question.prompt);
+        ui.text_edit_singleline(answer).labelled_by(prompt.id);
+    }
+    heading
+}
 
-    // Convert the projection matrix to RowMatrix4
-    let projection_matrix = RowMatrix4 {
-        x: Vector4 {
-            x: projection_matrix[(0, 0)],
-            y: projection_matrix[(0, 1)],
-            z: projection_matrix[(0, 2)],
-            w: projection_matrix[(0, 3)],
-        },
-        y: Vector4 {
-            x: projection_matrix[(1, 0)],
-            y: projection_matrix[(1, 1)],
-            z: projection_matrix[(1, 2)],
-            w: projection_matrix[(1, 3)],
-        },
-        z: Vector4 {
-            x: projection_matrix[(2, 0)],
-            y: projection_matrix[(2, 1)],
-            z: projection_matrix[(2, 2)],
-            w: projection_matrix[(2, 3)],
-        },
-        w: Vector4 {
-            x: projection_matrix[(3, 0)],
-            y: projection_matrix[(3, 1)],
-            z: projection_matrix[(3, 2)],
-            w: projection_matrix[(3, 3)],
-        },
+/// The target structure the user has to rebuild, shown in its own window next to the controls
+fn target_preview(ui: &Context, app_state: &mut AppState) {
+    let Some(texture) = app_state.preview.texture else {
+        return;
     };
-
-    (view_matrix, projection_matrix)
+    if !app_state.challenge.kind.shows_target() {
+        return;
+    }
+    egui::Window::new(app_state.catalog.get("window-target"))
+        .pivot(Align2::RIGHT_TOP)
+        .default_pos(ui.screen_rect().right_top() + Vec2::new(-10.0, 10.0))
+        .resizable(false)
+        .show(ui, |ui| {
+            let side = responsive_side(ui.ctx(), 0.25, PREVIEW_SIDE);
+            ui.image((texture, Vec2::splat(side)));
+            let mut spin_independently = app_state.preview.spin_independently;
+            if ui
+                .checkbox(
+                    &mut spin_independently,
+                    app_state.catalog.get("spin-independently"),
+                )
+                .changed()
+            {
+                app_state
+                    .preview
+                    .set_spin_independently(spin_independently, app_state.rotation);
+            }
+        });
 }
@@ -1,31 +1,235 @@
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use egui::{Context, Shadow, Visuals};
+use egui::{
+    epaint::{ImageDelta, TextureAtlas},
+    Context, TextureHandle, TextureId,
+};
 use egui_wgpu::{Renderer, ScreenDescriptor};
-use egui_winit::State;
-use transform_gizmo_egui::{mint::Quaternion, mint::Vector3, Gizmo, GizmoResult};
+use egui_winit::{accesskit_winit, State};
+use glam::{DQuat, DVec2, IVec3, Vec3};
+use transform_gizmo_egui::Gizmo;
 use wgpu::{CommandEncoder, Device, Queue, TextureFormat, TextureView};
-use winit::{event::WindowEvent, window::Window};
+use winit::{event::WindowEvent, event_loop::EventLoopProxy, keyboard::Key, window::Window};
+
+use crate::{
+    accessible::TextChallenge,
+    camera::{Camera, RotationAnimation},
+    challenge::{Challenge, ChallengeError, PALETTE},
+    locale::Catalog,
+    palette::PalettePreset,
+    picking::{grid_ray, pick, Pick},
+    preview::PreviewState,
+    session::{ChallengeSession, SessionState},
+    theme::Theme,
+    voxel::{Voxel, VoxelGrid},
+};
 
 /// This is the state for the EGUI application that we can use for informing how our shaders operate
 pub struct AppState {
     pub gizmo: Gizmo,
-    pub rotation: Quaternion<f64>,
+    /// Orientation of the grid. Change it through [`AppState::set_rotation`] or [`AppState::animate_rotation`] so running animations are handled
+    pub rotation: DQuat,
+    rotation_animation: Option<RotationAnimation>,
+    /// Snap gizmo rotations to 90 degree steps
+    pub snap_rotation: bool,
+    pub camera: Camera,
+    pub challenge: Challenge,
+    /// Side view of the target structure
+    pub preview: PreviewState,
+    /// The user's in-progress build. Kept on the CPU so it survives GPU device loss
+    pub grid: VoxelGrid,
+    /// Color of the next placed block
+    pub selected_color: Vec3,
+    /// Typed answer for challenges that ask a question
+    pub answer: String,
+    /// Timer, attempts and verdict for the current challenge
+    pub session: ChallengeSession,
+    /// Pointer position over the viewport in physical pixels. None while it is outside the window
+    pub pointer: Option<DVec2>,
+    /// Cell selected with the keyboard. Takes over from the pointer until the mouse moves again
+    pub keyboard_cursor: Option<IVec3>,
+    /// Answer the text version of the challenge instead of the visual one
+    pub text_mode: bool,
+    /// Text version of the current challenge, generated from the same seed
+    pub text_challenge: TextChallenge,
+    /// Typed answers to the text challenge's questions, in order
+    pub text_answers: Vec<String>,
+    /// Translations for every piece of text in the window
+    pub catalog: Catalog,
+    /// Colors blocks are drawn with. Only changes how they look, the grid keeps the standard colors
+    pub palette: PalettePreset,
+    /// Draw a different pattern on each block color
+    pub patterns: bool,
+    /// Style of the controls and color behind the grid
+    pub theme: Theme,
+    /// The theme's logo uploaded to egui. Replaced on every rebuild like the preview texture
+    pub logo: Option<TextureHandle>,
 }
 
 impl AppState {
-    pub fn new() -> Self {
+    pub fn new(challenge: Challenge, session: ChallengeSession, catalog: Catalog) -> Self {
+        let text_challenge = TextChallenge::new(&challenge, catalog);
         Self {
             gizmo: Gizmo::default(),
-            rotation: Quaternion {
-                v: Vector3 {
-                    x: 0.0,
-                    y: 0.0,
-                    z: 0.0,
-                },
-                s: 1.0,
-            },
+            camera: Camera::default(),
+            grid: challenge.start,
+            challenge,
+            preview: PreviewState::default(),
+            rotation: DQuat::IDENTITY,
+            rotation_animation: None,
+            snap_rotation: false,
+            selected_color: PALETTE[0],
+            answer: String::new(),
+            session,
+            pointer: None,
+            keyboard_cursor: None,
+            text_mode: false,
+            text_answers: vec![String::new(); text_challenge.questions.len()],
+            text_challenge,
+            catalog,
+            palette: PalettePreset::default(),
+            patterns: false,
+            theme: Theme::default(),
+            logo: None,
+        }
+    }
+
+    /// What the pointer is over in a viewport of the given size in physical pixels
+    pub fn hover(&mut self, viewport: DVec2) -> Option<Pick> {
+        let pointer = self.pointer?;
+        let (origin, direction) = grid_ray(
+            &mut self.camera,
+            &self.grid,
+            self.rotation,
+            pointer,
+            viewport,
+        );
+        pick(&self.grid, origin, direction)
+    }
+
+    /// Whether a block may go into `cell`. It has to be an empty cell inside the grid and allowed by the challenge while it is active
+    pub fn can_place(&self, cell: IVec3) -> bool {
+        self.session.state() == SessionState::Active
+            && VoxelGrid::in_bounds(cell.x, cell.y, cell.z)
+            && !self
+                .grid
+                .get(cell.x as usize, cell.y as usize, cell.z as usize)
+                .is_solid()
+            && self.challenge.allows_placement(&self.grid)
+    }
+
+    /// Whether the block in `cell` may be removed. There has to be one and the challenge has to allow removals while it is active
+    pub fn can_remove(&self, cell: IVec3) -> bool {
+        self.session.state() == SessionState::Active
+            && self.challenge.allows_removal()
+            && VoxelGrid::in_bounds(cell.x, cell.y, cell.z)
+            && self
+                .grid
+                .get(cell.x as usize, cell.y as usize, cell.z as usize)
+                .is_solid()
+    }
+
+    /// Places a block of the selected color in `cell` if allowed. Returns true if the grid changed
+    pub fn place_at(&mut self, cell: IVec3) -> bool {
+        if !self.can_place(cell) {
+            return false;
+        }
+        let voxel = Voxel::solid(self.selected_color);
+        self.grid
+            .set(cell.x as usize, cell.y as usize, cell.z as usize, voxel);
+        true
+    }
+
+    /// Removes the block in `cell` if allowed. Returns true if the grid changed
+    pub fn remove_at(&mut self, cell: IVec3) -> bool {
+        if !self.can_remove(cell) {
+            return false;
+        }
+        self.grid.set(
+            cell.x as usize,
+            cell.y as usize,
+            cell.z as usize,
+            Voxel::EMPTY,
+        );
+        true
+    }
+
+    /// Places a block where the pointer's ghost is. Returns true if the grid changed
+    pub fn place_block(&mut self, viewport: DVec2) -> bool {
+        self.hover(viewport)
+            .is_some_and(|pick| self.place_at(pick.placement()))
+    }
+
+    /// Removes the block under the pointer. Returns true if the grid changed
+    pub fn remove_block(&mut self, viewport: DVec2) -> bool {
+        self.hover(viewport)
+            .filter(|pick| pick.solid)
+            .is_some_and(|pick| self.remove_at(pick.cell))
+    }
+
+    /// Checks the current grid and answer against the challenge, or the typed answers against the text version in text mode
+    pub fn submit(&mut self) {
+        let correct = if self.text_mode {
+            self.text_challenge.verify(&self.text_answers)
+        } else {
+            self.challenge.verify(&self.grid, &self.answer)
+        };
+        self.session.submit(&self.challenge, correct);
+    }
+
+    /// Replaces the challenge with a fresh one after a failure or expiry, if attempts remain
+    pub fn retry(&mut self) -> Result<(), ChallengeError> {
+        if !self.session.can_retry() {
+            return Ok(());
+        }
+        let challenge = Challenge::new(self.challenge.spec.fresh())?;
+        self.session.begin_retry(&self.challenge);
+        self.challenge = challenge;
+        self.grid = self.challenge.start;
+        self.answer.clear();
+        self.text_challenge = TextChallenge::new(&self.challenge, self.catalog);
+        self.text_answers = vec![String::new(); self.text_challenge.questions.len()];
+        self.session.start(&self.challenge, Instant::now());
+        Ok(())
+    }
+
+    /// Sets the rotation immediately, cancelling any animation. Used for direct manipulation like dragging
+    pub fn set_rotation(&mut self, rotation: DQuat) {
+        self.rotation_animation = None;
+        self.rotation = rotation;
+    }
+
+    /// Starts turning the grid towards `target`
+    pub fn animate_rotation(&mut self, target: DQuat) {
+        // Take the short way around
+        let target = if self.rotation.dot(target) < 0.0 {
+            -target
+        } else {
+            target
+        };
+        self.rotation_animation = Some(RotationAnimation::new(self.rotation, target));
+    }
+
+    /// Where the grid is heading: the end of the running animation, or the current rotation
+    pub fn rotation_target(&self) -> DQuat {
+        self.rotation_animation
+            .map_or(self.rotation, |animation| animation.target())
+    }
+
+    /// Advances the rotation animation. Returns true while it still needs frames
+    pub fn step_animation(&mut self) -> bool {
+        let Some(animation) = self.rotation_animation else {
+            return false;
+        };
+        let (rotation, finished) = animation.sample(Instant::now());
+        self.rotation = rotation;
+        if finished {
+            self.rotation_animation = None;
         }
+        !finished
     }
 }
 
@@ -39,40 +243,120 @@ pub struct EguiRenderer {
 }
 
 impl EguiRenderer {
-    pub fn new(device: &Device, window: Arc<Window>) -> Self {
+    /// Creates the winit side of egui with AccessKit attached. Kept apart from the renderer so the screen reader connection survives rebuilding the GPU state
+    pub fn winit_state<T: From<accesskit_winit::Event> + Send>(
+        window: &Window,
+        proxy: EventLoopProxy<T>,
+    ) -> State {
         // Egui initializaiton. This is the first thing you need when working with egui. Context contains the InputState, Memory, PlatformOutput, and more.
         let ctx = Context::default();
         let id = ctx.viewport_id();
 
-        // Controls the visual style of egui
-        const BORDER_RADIUS: f32 = 2.0;
-        let visuals = Visuals {
-            window_rounding: egui::Rounding::same(BORDER_RADIUS),
-            window_shadow: Shadow::NONE,
-            // menu_rounding: todo!(),
-            ..Default::default()
-        };
+        // This is the basic state that handles integration between winit and egui
+        let mut egui_state = State::new(ctx, id, window, None, None, None);
+        // AccessKit can only attach to a window that hasn't been shown yet
+        egui_state.init_accesskit(window, proxy);
+        egui_state
+    }
 
-        ctx.set_visuals(visuals);
+    pub fn new(
+        device: &Device,
+        window: Arc<Window>,
+        output_format: TextureFormat,
+        mut app_state: AppState,
+        egui_state: State,
+    ) -> Self {
+        let ctx = egui_state.egui_ctx().clone();
 
-        // This is the basic state that handles integration between winit and egui
-        let egui_state = State::new(ctx.clone(), id, &window, None, None, None);
+        app_state.logo = app_state.theme.apply(&ctx);
+        // A context kept across a rebuild sent its font atlas to the old renderer and only sends changes from now on. Queue the whole atlas again so the new renderer has it
+        if ctx.cumulative_pass_nr() > 0 {
+            let atlas = ctx.fonts(|fonts| fonts.image());
+            ctx.tex_manager().write().set(
+                TextureId::default(),
+                ImageDelta::full(atlas, TextureAtlas::texture_options()),
+            );
+        }
 
         // These are the settings for the rendered. The format needed, dithering and sampling applied, etc... This is the simplest render possible
-        // ! THE TEXTURE FORMAT SHOULD NOT BE HARDCODED AND SHOULD DYNAMICALLY ADJUST TO WHAT'S IN THE PIPELINE
-        let egui_renderer = Renderer::new(device, TextureFormat::Bgra8UnormSrgb, None, 1, false);
+        // The output format has to match the surface the scene pipeline renders into
+        let egui_renderer = Renderer::new(device, output_format, None, 1, false);
 
         EguiRenderer {
             context: ctx,
             state: egui_state,
             window,
             renderer: egui_renderer,
-            app_state: AppState::new(),
+            app_state,
+        }
+    }
+
+    /// Consumes the renderer and hands back the winit state and app state so they can outlive the GPU resources
+    pub fn into_parts(self) -> (State, AppState) {
+        (self.state, self.app_state)
+    }
+
+    /// State shared between the UI and the rest of the app, such as the grid and the challenge
+    pub fn app_state_mut(&mut self) -> &mut AppState {
+        &mut self.app_state
+    }
+
+    /// Makes a texture rendered outside egui available to images in the UI
+    pub fn register_native_texture(&mut self, device: &Device, view: &TextureView) -> TextureId {
+        self.renderer
+            .register_native_texture(device, view, wgpu::FilterMode::Linear)
+    }
+
+    /// Feeds a window event to egui. Returns true if egui needs to repaint because of it
+    pub fn handle_input(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        self.state.on_window_event(window, event).repaint
+    }
+
+    /// Feeds a key egui didn't get as a window event, such as one from an input replay. Printable characters are typed into the focused text field too
+    pub fn handle_key(&mut self, key: &Key, pressed: bool, repeat: bool) {
+        let input = self.state.egui_input_mut();
+        let modifiers = input.modifiers;
+        let name = match key {
+            Key::Named(named) => format!("{named:?}"),
+            Key::Character(text) => text.to_uppercase(),
+            _ => return,
+        };
+        if let Some(key) = egui::Key::from_name(&name) {
+            input.events.push(egui::Event::Key {
+                key,
+                physical_key: None,
+                pressed,
+                repeat,
+                modifiers,
+            });
+        }
+        // Shortcuts like Ctrl+C don't type anything, matching what egui_winit does for real key events
+        if pressed
+            && !modifiers.command
+            && let Some(text) = key.to_text()
+            && text.chars().all(|c| !c.is_control())
+        {
+            input.events.push(egui::Event::Text(text.to_owned()));
         }
     }
 
-    pub fn handle_input(&mut self, window: &Window, event: &WindowEvent) {
-        let _ = self.state.on_window_event(window, event);
+    /// Feeds a request from a screen reader to egui. Returns true if egui needs to repaint because of it
+    pub fn handle_accesskit(&mut self, event: accesskit_winit::WindowEvent) -> bool {
+        match event {
+            // Egui only builds the accessibility tree once something asks for it, and sends it with the next frame
+            accesskit_winit::WindowEvent::InitialTreeRequested => {
+                self.context.enable_accesskit();
+                true
+            }
+            accesskit_winit::WindowEvent::ActionRequested(request) => {
+                self.state.on_accesskit_action_request(request);
+                true
+            }
+            accesskit_winit::WindowEvent::AccessibilityDeactivated => {
+                self.context.disable_accesskit();
+                false
+            }
+        }
     }
 
     pub fn draw(
@@ -81,19 +365,20 @@ impl EguiRenderer {
         queue: &Queue,
         // Static for let mut rpass = encoder.begin_render_pass(&render_pass_descriptor);
         encoder: &mut CommandEncoder,
-        window: &Window,
         window_surface_view: &TextureView,
-        screen_descriptor: ScreenDescriptor,
+        mut screen_descriptor: ScreenDescriptor,
         mut run_ui: impl FnMut(&Context, &mut AppState),
-    ) {
-        // self.state.set_pixels_per_point(window.scale_factor() as f32);
-        let raw_input = self.state.take_egui_input(window);
+    ) -> Duration {
+        // Egui-winit takes the window's scale factor into the input, and egui multiplies it with the user's zoom to get pixels per point
+        let raw_input = self.state.take_egui_input(&self.window);
         let full_output = self.context.run(raw_input, |_ui| {
             run_ui(&self.context, &mut self.app_state);
         });
+        // The caller only knows the scale factor. The meshes are laid out with the zoom applied too, so the renderer has to use the same scale
+        screen_descriptor.pixels_per_point = full_output.pixels_per_point;
 
         self.state
-            .handle_platform_output(window, full_output.platform_output);
+            .handle_platform_output(&self.window, full_output.platform_output);
 
         let tris = self
             .context
@@ -134,5 +419,11 @@ impl EguiRenderer {
         for x in &full_output.textures_delta.free {
             self.renderer.free_texture(x)
         }
+
+        // Animations and widgets that are being interacted with ask for a repaint through this. Duration::MAX means egui is idle
+        full_output
+            .viewport_output
+            .get(&self.context.viewport_id())
+            .map_or(Duration::MAX, |viewport| viewport.repaint_delay)
     }
 }
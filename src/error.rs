@@ -0,0 +1,153 @@
+use std::fmt;
+
+use wgpu::{CreateSurfaceError, RequestDeviceError, SurfaceError};
+use winit::error::{EventLoopError, OsError};
+
+use crate::{
+    capture::CaptureError, challenge::ChallengeError, replay::ReplayError, theme::ThemeError,
+    wgpu::AdapterError,
+};
+
+/// Every way MineCaptcha can fail to start or keep rendering. Hosts embedding the captcha should treat any of these as a reason to fall back to another challenge
+#[derive(Debug)]
+pub enum Error {
+    /// The winit event loop couldn't be created or exited abnormally
+    EventLoop(EventLoopError),
+    /// The OS refused to create the window
+    Window(OsError),
+    /// WGPU couldn't create a surface from the window handle
+    Surface(CreateSurfaceError),
+    /// No adapter matched the requested backend and options
+    Adapter(AdapterError),
+    /// The adapter couldn't provide a logical device
+    Device(RequestDeviceError),
+    /// The surface reports no texture formats for the chosen adapter
+    NoSurfaceFormat,
+    /// The surface and adapter don't agree on a configuration for the window size
+    SurfaceConfig { width: u32, height: u32 },
+    /// A frame couldn't be acquired from the surface and retrying won't help
+    Frame(SurfaceError),
+    /// The challenge file couldn't be read or parsed
+    Challenge(ChallengeError),
+    /// The theme file, or a font or logo it names, couldn't be read or parsed
+    Theme(ThemeError),
+    /// A screenshot or turntable couldn't be rendered or saved
+    Capture(CaptureError),
+    /// The input recording couldn't be created, read or parsed
+    Replay(ReplayError),
+}
+
+impl Error {
+    /// Process exit code for this error. Each variant gets its own code so scripts can tell failures apart
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::EventLoop(_) => 2,
+            Error::Window(_) => 3,
+            Error::Surface(_) => 4,
+            Error::Adapter(_) => 5,
+            Error::Device(_) => 6,
+            Error::NoSurfaceFormat | Error::SurfaceConfig { .. } => 7,
+            Error::Frame(_) => 8,
+            Error::Challenge(_) => 9,
+            Error::Theme(_) => 10,
+            Error::Capture(_) => 11,
+            Error::Replay(_) => 12,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::EventLoop(e) => write!(f, "Event loop failed: {e}"),
+            Error::Window(e) => write!(f, "Couldn't create window: {e}"),
+            Error::Surface(e) => write!(f, "Unable to get surface from window handle: {e}"),
+            Error::Adapter(e) => write!(f, "{e}"),
+            Error::Device(e) => write!(f, "Unable to get device and queue: {e}"),
+            Error::NoSurfaceFormat => write!(f, "The surface supports no texture formats"),
+            Error::SurfaceConfig { width, height } => {
+                write!(f, "Unable to configure the surface for {width}x{height}")
+            }
+            Error::Frame(e) => write!(f, "Failed to get frame: {e}"),
+            Error::Challenge(e) => write!(f, "{e}"),
+            Error::Theme(e) => write!(f, "{e}"),
+            Error::Capture(e) => write!(f, "{e}"),
+            Error::Replay(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::EventLoop(e) => Some(e),
+            Error::Window(e) => Some(e),
+            Error::Surface(e) => Some(e),
+            Error::Adapter(e) => Some(e),
+            Error::Device(e) => Some(e),
+            Error::Frame(e) => Some(e),
+            Error::Challenge(e) => Some(e),
+            Error::Theme(e) => Some(e),
+            Error::Capture(e) => Some(e),
+            Error::Replay(e) => Some(e),
+            Error::NoSurfaceFormat | Error::SurfaceConfig { .. } => None,
+        }
+    }
+}
+
+impl From<EventLoopError> for Error {
+    fn from(value: EventLoopError) -> Self {
+        Error::EventLoop(value)
+    }
+}
+
+impl From<OsError> for Error {
+    fn from(value: OsError) -> Self {
+        Error::Window(value)
+    }
+}
+
+impl From<CreateSurfaceError> for Error {
+    fn from(value: CreateSurfaceError) -> Self {
+        Error::Surface(value)
+    }
+}
+
+impl From<AdapterError> for Error {
+    fn from(value: AdapterError) -> Self {
+        Error::Adapter(value)
+    }
+}
+
+impl From<RequestDeviceError> for Error {
+    fn from(value: RequestDeviceError) -> Self {
+        Error::Device(value)
+    }
+}
+
+impl From<ChallengeError> for Error {
+    fn from(value: ChallengeError) -> Self {
+        Error::Challenge(value)
+    }
+}
+
+impl From<ThemeError> for Error {
+    fn from(value: ThemeError) -> Self {
+        Error::Theme(value)
+    }
+}
+
+impl From<CaptureError> for Error {
+    fn from(value: CaptureError) -> Self {
+        Error::Capture(value)
+    }
+}
+
+impl From<ReplayError> for Error {
+    fn from(value: ReplayError) -> Self {
+        Error::Replay(value)
+    }
+}
+
+/// Shorthand for results that fail with the crate [`Error`]
+pub type Result<T> = std::result::Result<T, Error>;
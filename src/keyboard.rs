@@ -0,0 +1,101 @@
+use std::f64::consts::FRAC_PI_2;
+
+use glam::{DQuat, DVec3, IVec3};
+use winit::{
+    event::{ElementState, WindowEvent},
+    keyboard::{Key, ModifiersState, NamedKey},
+};
+
+use crate::{capture::CaptureKind, egui_render::AppState, voxel::GRID_SIZE};
+
+/// Keyboard alternative to the mouse for accessibility. Arrow keys move a cursor across the grid relative to the screen, Page Up and Page Down change layer,
+/// Enter places a block, Delete or Backspace removes one, Q and E turn the grid by 90 degrees and W and S tilt it
+pub fn handle_event(event: &WindowEvent, app_state: &mut AppState) -> bool {
+    let WindowEvent::KeyboardInput { event, .. } = event else {
+        return false;
+    };
+    event.state == ElementState::Pressed && handle_key(event.logical_key.as_ref(), app_state)
+}
+
+/// Reacts to a key press the way [`handle_event`] does. Returns true if the app state changed
+pub fn handle_key(key: Key<&str>, app_state: &mut AppState) -> bool {
+    let right = app_state.camera.right();
+    let forward = -app_state.camera.view_direction();
+    match key {
+        Key::Named(NamedKey::ArrowLeft) => move_cursor(app_state, -grid_axis(app_state, right)),
+        Key::Named(NamedKey::ArrowRight) => move_cursor(app_state, grid_axis(app_state, right)),
+        // Up on screen plus away from the camera always has some horizontal part, even when looking straight down
+        Key::Named(NamedKey::ArrowUp) => {
+            let away = right.cross(forward) + forward;
+            move_cursor(app_state, grid_axis(app_state, away))
+        }
+        Key::Named(NamedKey::ArrowDown) => {
+            let away = right.cross(forward) + forward;
+            move_cursor(app_state, -grid_axis(app_state, away))
+        }
+        Key::Named(NamedKey::PageUp) => move_cursor(app_state, IVec3::Y),
+        Key::Named(NamedKey::PageDown) => move_cursor(app_state, IVec3::NEG_Y),
+        Key::Named(NamedKey::Enter) => app_state
+            .keyboard_cursor
+            .is_some_and(|cell| app_state.place_at(cell)),
+        Key::Named(NamedKey::Delete | NamedKey::Backspace) => app_state
+            .keyboard_cursor
+            .is_some_and(|cell| app_state.remove_at(cell)),
+        Key::Character("q" | "Q") => turn(app_state, DQuat::from_rotation_y(-FRAC_PI_2)),
+        Key::Character("e" | "E") => turn(app_state, DQuat::from_rotation_y(FRAC_PI_2)),
+        Key::Character("w" | "W") => turn(app_state, DQuat::from_axis_angle(right, -FRAC_PI_2)),
+        Key::Character("s" | "S") => turn(app_state, DQuat::from_axis_angle(right, FRAC_PI_2)),
+        _ => false,
+    }
+}
+
+/// F12 asks for a screenshot and Shift+F12 for a turntable. The window makes them since captures need the GPU
+pub fn capture_request(event: &WindowEvent, modifiers: ModifiersState) -> Option<CaptureKind> {
+    let WindowEvent::KeyboardInput { event, .. } = event else {
+        return None;
+    };
+    if event.state != ElementState::Pressed {
+        return None;
+    }
+    capture_key(event.logical_key.as_ref(), event.repeat, modifiers)
+}
+
+/// The capture asked for by a key press, as in [`capture_request`]
+pub fn capture_key(key: Key<&str>, repeat: bool, modifiers: ModifiersState) -> Option<CaptureKind> {
+    // Holding the key down shouldn't fill the directory with captures
+    if repeat {
+        return None;
+    }
+    match key {
+        Key::Named(NamedKey::F12) if modifiers.shift_key() => Some(CaptureKind::Turntable),
+        Key::Named(NamedKey::F12) => Some(CaptureKind::Screenshot),
+        _ => None,
+    }
+}
+
+/// Moves the keyboard cursor one cell, staying inside the grid. The first key press shows the cursor in the middle of the floor instead
+fn move_cursor(app_state: &mut AppState, step: IVec3) -> bool {
+    let center = GRID_SIZE as i32 / 2;
+    app_state.keyboard_cursor = Some(match app_state.keyboard_cursor {
+        Some(cell) => (cell + step).clamp(IVec3::ZERO, IVec3::splat(GRID_SIZE as i32 - 1)),
+        None => IVec3::new(center, 0, center),
+    });
+    true
+}
+
+/// Turns the grid by `turn` in world space on top of wherever it is heading
+fn turn(app_state: &mut AppState, turn: DQuat) -> bool {
+    let target = app_state.rotation_target();
+    app_state.animate_rotation((turn * target).normalize());
+    true
+}
+
+/// The horizontal grid axis that points closest to `world` once the grid's rotation is taken into account
+fn grid_axis(app_state: &AppState, world: DVec3) -> IVec3 {
+    let local = app_state.rotation.inverse() * world;
+    if local.x.abs() >= local.z.abs() {
+        IVec3::new(local.x.signum() as i32, 0, 0)
+    } else {
+        IVec3::new(0, 0, local.z.signum() as i32)
+    }
+}
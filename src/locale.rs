@@ -0,0 +1,242 @@
+use std::{collections::HashMap, fmt, sync::OnceLock};
+
+/// Catalogs compiled into the binary, by language code. Each line is `key = value`, lines starting with `#` are comments and `{name}` in a value is replaced by [`Catalog::format`]
+const CATALOGS: [(&str, &str); 4] = [
+    (FALLBACK, include_str!("locales/en.txt")),
+    ("de", include_str!("locales/de.txt")),
+    ("es", include_str!("locales/es.txt")),
+    ("fr", include_str!("locales/fr.txt")),
+];
+
+/// Language used when the requested one has no catalog, and for keys a catalog is missing
+const FALLBACK: &str = "en";
+
+type Messages = HashMap<&'static str, &'static str>;
+
+/// Every catalog parsed once, on first use
+fn catalogs() -> &'static HashMap<&'static str, Messages> {
+    static CACHE: OnceLock<HashMap<&'static str, Messages>> = OnceLock::new();
+    CACHE.get_or_init(|| {
+        CATALOGS
+            .iter()
+            .map(|&(language, source)| (language, parse(source)))
+            .collect()
+    })
+}
+
+fn parse(source: &'static str) -> Messages {
+    source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            Some((key.trim(), value.trim()))
+        })
+        .collect()
+}
+
+/// The translated strings for one language. Every piece of text shown in the window is looked up here by key. Cheap to copy so UI code can hold one while mutating the app state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Catalog {
+    language: &'static str,
+}
+
+impl Default for Catalog {
+    fn default() -> Self {
+        Self { language: FALLBACK }
+    }
+}
+
+impl Catalog {
+    /// Picks the catalog for a locale such as `de`, `de-AT` or `de_DE.UTF-8`. Falls back to English when there is none
+    pub fn new(locale: Option<&str>) -> Self {
+        let Some(locale) = locale else {
+            return Self::default();
+        };
+        let language = locale
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match CATALOGS.iter().find(|(code, _)| *code == language) {
+            Some(&(language, _)) => Self { language },
+            // The POSIX default locale just means nothing was configured
+            None if language == "c" || language == "posix" => Self::default(),
+            None => {
+                log::warn!("No translation for locale {locale}. Using English");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn language(self) -> &'static str {
+        self.language
+    }
+
+    /// The text for `key`. Falls back to English, then to the key itself so a missing string is visible instead of blank
+    pub fn get(self, key: &'static str) -> &'static str {
+        let catalogs = catalogs();
+        [self.language, FALLBACK]
+            .iter()
+            .find_map(|language| catalogs[language].get(key).copied())
+            .unwrap_or_else(|| {
+                log::warn!("Missing translation key {key}");
+                key
+            })
+    }
+
+    /// The text for `key` with each `{name}` replaced by the matching argument
+    pub fn format(self, key: &'static str, args: &[(&str, &dyn fmt::Display)]) -> String {
+        args.iter()
+            .fold(self.get(key).to_owned(), |text, (name, value)| {
+                text.replace(&format!("{{{name}}}"), &value.to_string())
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{camera::ViewPreset, challenge::COLOR_NAME_KEYS, palette::PalettePreset};
+
+    /// Source files that put text on screen. Every string literal in them has to be a catalog key, a placeholder name or listed in [`NOT_TEXT`]
+    const UI_SOURCES: [(&str, &str); 4] = [
+        ("egui.rs", include_str!("egui.rs")),
+        ("accessible.rs", include_str!("accessible.rs")),
+        ("win.rs", include_str!("win.rs")),
+        ("challenge.rs", include_str!("challenge.rs")),
+    ];
+
+    /// Literals in the UI sources that are never shown: widget ids, format strings and separators
+    const NOT_TEXT: [&str; 3] = ["palette", "{e}", "; "];
+
+    /// Calls whose arguments only reach logs, the terminal or GPU debuggers, never the window
+    const DIAGNOSTICS: [&str; 12] = [
+        "log::trace!(",
+        "log::debug!(",
+        "log::info!(",
+        "log::warn!(",
+        "log::error!(",
+        "println!(",
+        "eprintln!(",
+        "print!(",
+        "write!(",
+        "writeln!(",
+        ".expect(",
+        "label: Some(",
+    ];
+
+    /// Contents of every plain string literal in `source` outside of tests and [`DIAGNOSTICS`], with escapes left as written. Skips comments and char literals
+    fn string_literals(source: &str) -> Vec<&str> {
+        let code = source.split("#[cfg(test)]").next().unwrap_or(source);
+        let bytes = code.as_bytes();
+        let mut literals = Vec::new();
+        // Parentheses open right now, and how many were open when a diagnostic call started
+        let mut depth = 0;
+        let mut diagnostic_depth = None;
+        let mut i = 0;
+        while i < bytes.len() {
+            if diagnostic_depth.is_none()
+                && let Some(call) = DIAGNOSTICS.iter().find(|call| code[i..].starts_with(*call))
+            {
+                diagnostic_depth = Some(depth);
+                depth += 1;
+                i += call.len();
+                continue;
+            }
+            match bytes[i] {
+                b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                    i = code[i..].find('\n').map_or(bytes.len(), |end| i + end);
+                }
+                b'\'' if bytes.get(i + 2) == Some(&b'\'') => i += 3,
+                b'\'' if bytes.get(i + 1) == Some(&b'\\') => i += 4,
+                b'(' => {
+                    depth += 1;
+                    i += 1;
+                }
+                b')' => {
+                    depth -= 1;
+                    if diagnostic_depth == Some(depth) {
+                        diagnostic_depth = None;
+                    }
+                    i += 1;
+                }
+                b'"' => {
+                    let start = i + 1;
+                    i = start;
+                    while i < bytes.len() && bytes[i] != b'"' {
+                        i += if bytes[i] == b'\\' { 2 } else { 1 };
+                    }
+                    if diagnostic_depth.is_none() {
+                        literals.push(&code[start..i.min(code.len())]);
+                    }
+                    i += 1;
+                }
+                _ => i += 1,
+            }
+        }
+        literals
+    }
+
+    #[test]
+    fn every_catalog_has_every_key() {
+        let catalogs = catalogs();
+        let english = &catalogs[FALLBACK];
+        for (language, messages) in catalogs {
+            for key in english.keys() {
+                assert!(messages.contains_key(key), "{language} is missing {key}");
+            }
+            for key in messages.keys() {
+                assert!(
+                    english.contains_key(key),
+                    "{language} has unknown key {key}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn no_ui_string_bypasses_the_catalog() {
+        let english = &catalogs()[FALLBACK];
+        let is_placeholder = |literal: &str| {
+            let placeholder = format!("{{{literal}}}");
+            english.values().any(|value| value.contains(&placeholder))
+        };
+        for (file, source) in UI_SOURCES {
+            for literal in string_literals(source) {
+                assert!(
+                    english.contains_key(literal)
+                        || is_placeholder(literal)
+                        || NOT_TEXT.contains(&literal),
+                    "{file} shows {literal:?} without going through the catalog"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn generated_keys_exist() {
+        let english = &catalogs()[FALLBACK];
+        let keys = ViewPreset::ALL
+            .map(ViewPreset::label_key)
+            .into_iter()
+            .chain(PalettePreset::ALL.map(PalettePreset::label_key))
+            .chain(COLOR_NAME_KEYS);
+        for key in keys {
+            assert!(english.contains_key(key), "missing {key}");
+        }
+    }
+
+    #[test]
+    fn locales_fall_back_to_english() {
+        assert_eq!(Catalog::new(Some("de_DE.UTF-8")).language(), "de");
+        assert_eq!(Catalog::new(Some("es-MX")).language(), "es");
+        assert_eq!(Catalog::new(Some("xx")).language(), FALLBACK);
+        assert_eq!(Catalog::new(None).language(), FALLBACK);
+        assert_eq!(
+            Catalog::default().format("time-left", &[("seconds", &42)]),
+            "Time left: 42s"
+        );
+    }
+}
@@ -1,24 +1,77 @@
 #![feature(let_chains)]
 #![feature(const_trait_impl)]
 
-use win::Win;
-use winit::{
-    error::EventLoopError,
-    event_loop::{ControlFlow, EventLoop},
-};
+use std::process::ExitCode;
 
+use calibration::CalibrationReport;
+use clap::Parser;
+use cli::Args;
+use theme::Theme;
+use win::{UserEvent, Win};
+use winit::event_loop::{ControlFlow, EventLoop};
+
+mod accessible;
+mod calibration;
+mod camera;
+mod capture;
+mod challenge;
+mod cli;
+mod difficulty;
 mod egui;
 mod egui_render;
+mod error;
+mod keyboard;
+mod locale;
+mod orbit;
+mod palette;
+mod picking;
+mod preview;
+mod replay;
+mod session;
+mod theme;
+mod uniforms;
+mod voxel;
 mod wgpu;
 mod win;
 
-fn main() -> Result<(), EventLoopError> {
-    let event_loop = EventLoop::new()?;
-    let mut app = Win::default();
+fn main() -> ExitCode {
+    env_logger::init();
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("MineCaptcha failed: {e}");
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+fn run(args: Args) -> error::Result<()> {
+    if let Some(count) = args.calibrate {
+        print!(
+            "{}",
+            CalibrationReport::generate(&args.challenge_spec(), count)?
+        );
+        return Ok(());
+    }
+    let theme = match &args.theme {
+        Some(path) => Theme::load(path)?,
+        None => Theme::default(),
+    };
+    if args.screenshot.is_some() || args.turntable.is_some() {
+        return capture::run(&args, &theme);
+    }
+    // AccessKit talks to the window through user events, so the loop needs a proxy for them
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut app = Win::new(args, theme, event_loop.create_proxy());
     // ControlFlow::Wait pauses the event loop if no events are available to process
     // ControlFlow::Poll continuously runs the event loop
     event_loop.set_control_flow(ControlFlow::Wait);
 
     // Runs the window references as app within the selected event loop.
-    event_loop.run_app(&mut app)
+    event_loop.run_app(&mut app)?;
+    // Errors raised inside the loop are stored on the app because winit's callbacks can't return them
+    match app.take_error() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
 }
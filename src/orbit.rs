@@ -0,0 +1,138 @@
+use glam::{DQuat, DVec2};
+use winit::{
+    event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::Key,
+};
+
+use crate::egui_render::AppState;
+
+/// Radians of rotation per pixel dragged
+const ORBIT_SPEED: f64 = 0.01;
+/// Zoom factor applied per scroll wheel line
+const ZOOM_STEP: f64 = 0.9;
+/// How far the pointer has to move before a press counts as a drag instead of a click
+const DRAG_THRESHOLD: f64 = 3.0;
+
+/// What the current drag does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DragMode {
+    Orbit,
+    Pan,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Drag {
+    mode: DragMode,
+    start: DVec2,
+    /// Set once the pointer has moved past [`DRAG_THRESHOLD`]
+    moved: bool,
+}
+
+/// Turns mouse input in the 3D viewport into camera movement and building. Left drag orbits, middle drag pans, the wheel zooms and R resets the view.
+/// A left click without dragging places a block and a right click removes one.
+/// Orbiting turns the grid through `AppState.rotation` so the gizmo always shows the same orientation
+#[derive(Debug, Default)]
+pub struct OrbitController {
+    cursor: DVec2,
+    drag: Option<Drag>,
+}
+
+impl OrbitController {
+    /// Handles a window event that egui didn't claim. Returns true if the camera or rotation changed and a redraw is needed
+    pub fn handle_event(
+        &mut self,
+        event: &WindowEvent,
+        app_state: &mut AppState,
+        viewport: DVec2,
+    ) -> bool {
+        match event {
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Right,
+                ..
+            } => app_state.remove_block(viewport),
+            WindowEvent::MouseInput { state, button, .. } => {
+                let mode = match button {
+                    MouseButton::Left => DragMode::Orbit,
+                    MouseButton::Middle => DragMode::Pan,
+                    _ => return false,
+                };
+                match state {
+                    ElementState::Pressed => {
+                        self.drag = Some(Drag {
+                            mode,
+                            start: self.cursor,
+                            moved: false,
+                        })
+                    }
+                    ElementState::Released => {
+                        if let Some(drag) = self.drag.filter(|drag| drag.mode == mode) {
+                            self.drag = None;
+                            // A press that never turned into a drag is a click
+                            if drag.mode == DragMode::Orbit && !drag.moved {
+                                return app_state.place_block(viewport);
+                            }
+                        }
+                    }
+                }
+                false
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = DVec2::new(position.x, position.y);
+                let delta = position - self.cursor;
+                self.cursor = position;
+                // Moving the mouse hands placement back from the keyboard cursor
+                let had_keyboard_cursor = app_state.keyboard_cursor.take().is_some();
+                // The ghost follows the pointer, but only redraw when it actually lands on another cell
+                let hover = app_state.hover(viewport);
+                app_state.pointer = Some(position);
+                let hover_changed = had_keyboard_cursor || app_state.hover(viewport) != hover;
+                let Some(drag) = self.drag.as_mut() else {
+                    return hover_changed;
+                };
+                if !drag.moved && position.distance(drag.start) < DRAG_THRESHOLD {
+                    return hover_changed;
+                }
+                drag.moved = true;
+                match drag.mode {
+                    DragMode::Orbit => {
+                        // Horizontal drags spin the grid around the world up axis, vertical drags tip it towards or away from the camera
+                        let yaw = DQuat::from_rotation_y(delta.x * ORBIT_SPEED);
+                        let pitch =
+                            DQuat::from_axis_angle(app_state.camera.right(), delta.y * ORBIT_SPEED);
+                        app_state.set_rotation((pitch * yaw * app_state.rotation).normalize());
+                    }
+                    DragMode::Pan => {
+                        let scale = app_state.camera.world_units_per_pixel(viewport.y);
+                        // Dragging moves the scene with the pointer, so the camera goes the other way
+                        app_state.camera.pan(-delta.x * scale, delta.y * scale);
+                    }
+                }
+                true
+            }
+            WindowEvent::CursorLeft { .. } => {
+                let had_pointer = app_state.pointer.is_some();
+                app_state.pointer = None;
+                had_pointer
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y as f64,
+                    // Roughly one line per 50 pixels on touchpads
+                    MouseScrollDelta::PixelDelta(position) => position.y / 50.0,
+                };
+                app_state.camera.zoom(ZOOM_STEP.powf(lines));
+                true
+            }
+            WindowEvent::KeyboardInput { event, .. }
+                if event.state == ElementState::Pressed
+                    && matches!(event.logical_key.as_ref(), Key::Character("r" | "R")) =>
+            {
+                app_state.camera.reset_view();
+                app_state.animate_rotation(DQuat::IDENTITY);
+                true
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use clap::ValueEnum;
+use glam::Vec3;
+
+use crate::challenge::PALETTE;
+
+/// How the block colors are drawn. Voxels always keep the [`PALETTE`] colors so challenges and answers don't depend on the preset, the shader swaps them for the preset's colors when drawing
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PalettePreset {
+    #[default]
+    Standard,
+    /// Green weak vision, the most common kind of color blindness
+    Deuteranopia,
+    /// Red weak vision. Reds look darker, so they are lightened
+    Protanopia,
+    /// Blue weak vision, where blues and greens and yellows and pinks get confused
+    Tritanopia,
+}
+
+impl PalettePreset {
+    pub const ALL: [PalettePreset; 4] = [
+        PalettePreset::Standard,
+        PalettePreset::Deuteranopia,
+        PalettePreset::Protanopia,
+        PalettePreset::Tritanopia,
+    ];
+
+    /// Catalog key of the name shown in the controls window
+    pub fn label_key(self) -> &'static str {
+        match self {
+            PalettePreset::Standard => "palette-standard",
+            PalettePreset::Deuteranopia => "palette-deuteranopia",
+            PalettePreset::Protanopia => "palette-protanopia",
+            PalettePreset::Tritanopia => "palette-tritanopia",
+        }
+    }
+
+    /// Colors drawn in place of each [`PALETTE`] entry. They stay close enough to the original hues that the color names in instructions still fit, and differ in lightness so they can be told apart without hue. Based on the Okabe-Ito palette
+    pub fn colors(self) -> [Vec3; 6] {
+        match self {
+            PalettePreset::Standard => PALETTE,
+            PalettePreset::Deuteranopia => [
+                Vec3::new(0.84, 0.37, 0.0),
+                Vec3::new(0.94, 0.89, 0.26),
+                Vec3::new(0.0, 0.62, 0.45),
+                Vec3::new(0.8, 0.47, 0.65),
+                Vec3::new(0.96, 0.96, 0.96),
+                Vec3::new(0.15, 0.15, 0.15),
+            ],
+            PalettePreset::Protanopia => [
+                Vec3::new(0.9, 0.5, 0.1),
+                Vec3::new(0.98, 0.93, 0.45),
+                Vec3::new(0.1, 0.55, 0.65),
+                Vec3::new(0.45, 0.3, 0.8),
+                Vec3::new(0.96, 0.96, 0.96),
+                Vec3::new(0.15, 0.15, 0.15),
+            ],
+            PalettePreset::Tritanopia => [
+                Vec3::new(0.84, 0.16, 0.16),
+                Vec3::new(0.9, 0.7, 0.1),
+                Vec3::new(0.05, 0.55, 0.35),
+                Vec3::new(0.55, 0.1, 0.55),
+                Vec3::new(1.0, 1.0, 1.0),
+                Vec3::new(0.15, 0.15, 0.15),
+            ],
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use glam::{DQuat, DVec2, DVec3, IVec3};
+
+use crate::{
+    camera::Camera,
+    voxel::{VoxelGrid, GRID_SIZE},
+};
+
+/// What the pointer is over in the grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pick {
+    /// The solid voxel under the pointer, or the floor cell when no voxel was hit
+    pub cell: IVec3,
+    /// Normal of the face the ray entered `cell` through
+    pub normal: IVec3,
+    /// False when the ray went through the grid and landed on the floor
+    pub solid: bool,
+}
+
+impl Pick {
+    /// Cell a new block would go into. Next to the hovered face for voxels, the floor cell itself otherwise. May lie outside the grid
+    pub fn placement(&self) -> IVec3 {
+        if self.solid {
+            self.cell + self.normal
+        } else {
+            self.cell
+        }
+    }
+}
+
+/// Ray through a pixel of the viewport in grid space, matching how the voxel shader builds its rays. Returns the origin and normalized direction
+pub fn grid_ray(
+    camera: &mut Camera,
+    grid: &VoxelGrid,
+    rotation: DQuat,
+    cursor: DVec2,
+    viewport: DVec2,
+) -> (DVec3, DVec3) {
+    let view_model = camera.view_matrix() * grid.model_matrix(rotation);
+    let inverse_view_projection = (camera.projection.matrix() * view_model).inverse();
+    let origin = view_model.inverse().transform_point3(DVec3::ZERO);
+    // Pixel y grows downwards while clip space y grows upwards
+    let clip = DVec3::new(
+        cursor.x / viewport.x * 2.0 - 1.0,
+        1.0 - cursor.y / viewport.y * 2.0,
+        1.0,
+    );
+    let far_point = inverse_view_projection.project_point3(clip);
+    (origin, (far_point - origin).normalize())
+}
+
+/// Walks the ray through the grid like `marchGrid` in the voxel shader. Falls back to the floor so the first block can be placed on an empty grid
+pub fn pick(grid: &VoxelGrid, origin: DVec3, direction: DVec3) -> Option<Pick> {
+    let size = GRID_SIZE as f64;
+    let inverse = direction.recip();
+    let t0 = -origin * inverse;
+    let t1 = (DVec3::splat(size) - origin) * inverse;
+    let near = t0.min(t1);
+    let entry = near.max_element().max(0.0);
+    let exit = t0.max(t1).min_element();
+    if entry <= exit {
+        // Nudge the start inside the grid so floor() lands on the first cell
+        let start = origin + direction * (entry + 1e-6);
+        let mut cell = start
+            .floor()
+            .as_ivec3()
+            .clamp(IVec3::ZERO, IVec3::splat(GRID_SIZE as i32 - 1));
+        // Axes the ray doesn't move along never step since their boundary distance is infinite
+        let step = direction.signum().as_ivec3();
+        let delta = inverse.abs();
+        let mut side = (cell.as_dvec3() + step.max(IVec3::ZERO).as_dvec3() - start) * inverse;
+        // The face the ray entered through is the one whose slab was crossed last
+        let axis = largest_axis(near);
+        let mut normal = IVec3::ZERO;
+        normal[axis] = -step[axis];
+        while VoxelGrid::in_bounds(cell.x, cell.y, cell.z) {
+            if grid
+                .get(cell.x as usize, cell.y as usize, cell.z as usize)
+                .is_solid()
+            {
+                return Some(Pick {
+                    cell,
+                    normal,
+                    solid: true,
+                });
+            }
+            let axis = largest_axis(-side);
+            side[axis] += delta[axis];
+            cell[axis] += step[axis];
+            normal = IVec3::ZERO;
+            normal[axis] = -step[axis];
+        }
+    }
+
+    if direction.y < 0.0 {
+        let floor_point = origin + direction * (-origin.y / direction.y);
+        if (0.0..size).contains(&floor_point.x) && (0.0..size).contains(&floor_point.z) {
+            return Some(Pick {
+                cell: IVec3::new(floor_point.x as i32, 0, floor_point.z as i32),
+                normal: IVec3::Y,
+                solid: false,
+            });
+        }
+    }
+    None
+}
+
+/// Index of the largest component. Ties go to the earlier axis
+fn largest_axis(v: DVec3) -> usize {
+    if v.x >= v.y && v.x >= v.z {
+        0
+    } else if v.y >= v.z {
+        1
+    } else {
+        2
+    }
+}
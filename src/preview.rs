@@ -0,0 +1,153 @@
+use std::time::Instant;
+
+use glam::DQuat;
+use wgpu::{
+    BindGroupLayout, CommandEncoder, Device, RenderPipeline, Texture, TextureFormat, TextureView,
+};
+
+use crate::{
+    camera::Camera,
+    uniforms::{ScreenUniform, UniformBindings},
+    voxel::VoxelGrid,
+};
+
+/// Width and height of the preview texture in pixels
+pub const PREVIEW_SIZE: u32 = 256;
+/// Radians per second the preview turns when spinning on its own
+const SPIN_SPEED: f64 = 0.8;
+
+/// UI side of the target preview. Lives in the app state so it survives GPU rebuilds, only the texture id has to be replaced
+#[derive(Debug)]
+pub struct PreviewState {
+    /// Turn the target on its own instead of following the main view
+    pub spin_independently: bool,
+    /// Orientation of the target while spinning independently
+    spin_rotation: DQuat,
+    /// When the spin last advanced, so its speed doesn't depend on the frame rate
+    last_step: Option<Instant>,
+    /// Fixed camera for the preview. Zooming and panning the main view don't move it, only the rotation is shared
+    pub camera: Camera,
+    /// The rendered preview registered with egui. None until the GPU state exists
+    pub texture: Option<egui::TextureId>,
+}
+
+impl Default for PreviewState {
+    fn default() -> Self {
+        let mut camera = Camera::default();
+        camera.projection.set_aspect_ratio(1.0);
+        Self {
+            spin_independently: false,
+            spin_rotation: DQuat::IDENTITY,
+            last_step: None,
+            camera,
+            texture: None,
+        }
+    }
+}
+
+impl PreviewState {
+    /// Advances the independent spin. Returns true while spinning so frames keep coming
+    pub fn step(&mut self) -> bool {
+        let now = Instant::now();
+        let last_step = self.last_step.replace(now);
+        if !self.spin_independently {
+            self.last_step = None;
+            return false;
+        }
+        if let Some(last_step) = last_step {
+            let angle = now.duration_since(last_step).as_secs_f64() * SPIN_SPEED;
+            self.spin_rotation = (DQuat::from_rotation_y(angle) * self.spin_rotation).normalize();
+        }
+        true
+    }
+
+    /// Orientation to draw the target with, given the main view's rotation
+    pub fn rotation(&self, main_rotation: DQuat) -> DQuat {
+        if self.spin_independently {
+            self.spin_rotation
+        } else {
+            main_rotation
+        }
+    }
+
+    /// Switches between following the main view and spinning. Spinning starts from wherever the main view is so the target doesn't jump
+    pub fn set_spin_independently(&mut self, spin_independently: bool, main_rotation: DQuat) {
+        if spin_independently && !self.spin_independently {
+            self.spin_rotation = main_rotation;
+        }
+        self.spin_independently = spin_independently;
+    }
+}
+
+/// GPU side of the target preview. The target is raymarched into its own texture with the scene pipeline, then egui shows that texture as an image
+pub struct TargetPreview {
+    /// Kept alive for the view below
+    _texture: Texture,
+    pub view: TextureView,
+    pub bindings: UniformBindings,
+}
+
+impl TargetPreview {
+    /// Creates the render target and uniforms, starting out with `target`. Retrying swaps in a new challenge, so the grid is written again with the other uniforms every frame
+    pub fn new(
+        device: &Device,
+        layout: &BindGroupLayout,
+        format: TextureFormat,
+        target: &VoxelGrid,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Target Preview Texture"),
+            size: wgpu::Extent3d {
+                width: PREVIEW_SIZE,
+                height: PREVIEW_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            // Rendered to by the voxel pipeline and then sampled by egui
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bindings = UniformBindings::new(
+            device,
+            layout,
+            "Target Preview",
+            ScreenUniform::new(PREVIEW_SIZE, PREVIEW_SIZE),
+            target,
+        );
+        Self {
+            _texture: texture,
+            view,
+            bindings,
+        }
+    }
+
+    /// Raymarches the target into the preview texture over `background`. Has to be encoded before egui samples the texture
+    pub fn render(
+        &self,
+        encoder: &mut CommandEncoder,
+        pipeline: &RenderPipeline,
+        background: wgpu::Color,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Target Preview Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(background),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.bindings.bind_group, &[]);
+        render_pass.draw(0..6, 0..1);
+    }
+}
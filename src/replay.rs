@@ -0,0 +1,379 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::{self, File},
+    io::{self, LineWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use winit::{
+    dpi::PhysicalPosition,
+    event::{DeviceId, ElementState, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent},
+    keyboard::{Key, ModifiersState, NamedKey},
+};
+
+/// Named keys a recording can hold: every key the app reacts to plus the ones used to edit text fields
+const NAMED_KEYS: [NamedKey; 15] = [
+    NamedKey::ArrowLeft,
+    NamedKey::ArrowRight,
+    NamedKey::ArrowUp,
+    NamedKey::ArrowDown,
+    NamedKey::PageUp,
+    NamedKey::PageDown,
+    NamedKey::Home,
+    NamedKey::End,
+    NamedKey::Enter,
+    NamedKey::Delete,
+    NamedKey::Backspace,
+    NamedKey::Tab,
+    NamedKey::Escape,
+    NamedKey::Space,
+    NamedKey::F12,
+];
+
+/// Mouse buttons a recording can hold, with the names they are written as
+const BUTTONS: [(MouseButton, &str); 5] = [
+    (MouseButton::Left, "left"),
+    (MouseButton::Right, "right"),
+    (MouseButton::Middle, "middle"),
+    (MouseButton::Back, "back"),
+    (MouseButton::Forward, "forward"),
+];
+
+/// One piece of user input, as recorded with `--record` and played back with `--replay`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    /// The pointer moved to this position in physical pixels
+    Cursor(PhysicalPosition<f64>),
+    Button(MouseButton, ElementState),
+    Wheel(MouseScrollDelta),
+    /// A key went down or up. Only the logical key is kept, so replays don't depend on the keyboard layout
+    Key {
+        key: Key,
+        state: ElementState,
+        repeat: bool,
+    },
+    Modifiers(ModifiersState),
+}
+
+impl Input {
+    /// The input carried by a window event. None for events that aren't input, and for keys and buttons a recording can't hold
+    pub fn from_event(event: &WindowEvent) -> Option<Self> {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => Some(Input::Cursor(*position)),
+            WindowEvent::MouseInput { state, button, .. } => BUTTONS
+                .iter()
+                .any(|(known, _)| known == button)
+                .then_some(Input::Button(*button, *state)),
+            WindowEvent::MouseWheel { delta, .. } => Some(Input::Wheel(*delta)),
+            WindowEvent::KeyboardInput { event, .. } => {
+                let recordable = match &event.logical_key {
+                    Key::Named(named) => NAMED_KEYS.contains(named),
+                    Key::Character(text) => !text.chars().any(char::is_whitespace),
+                    _ => false,
+                };
+                recordable.then(|| Input::Key {
+                    key: event.logical_key.clone(),
+                    state: event.state,
+                    repeat: event.repeat,
+                })
+            }
+            WindowEvent::ModifiersChanged(modifiers) => Some(Input::Modifiers(modifiers.state())),
+            _ => None,
+        }
+    }
+
+    /// The window event that delivers this input. None for keys, since winit doesn't allow key events to be built outside of it
+    pub fn to_event(&self) -> Option<WindowEvent> {
+        let device_id = DeviceId::dummy();
+        match *self {
+            Input::Cursor(position) => Some(WindowEvent::CursorMoved {
+                device_id,
+                position,
+            }),
+            Input::Button(button, state) => Some(WindowEvent::MouseInput {
+                device_id,
+                state,
+                button,
+            }),
+            Input::Wheel(delta) => Some(WindowEvent::MouseWheel {
+                device_id,
+                delta,
+                phase: TouchPhase::Moved,
+            }),
+            Input::Key { .. } => None,
+            Input::Modifiers(state) => Some(WindowEvent::ModifiersChanged(state.into())),
+        }
+    }
+
+    /// Parses the part of a recording line after the time. None if it isn't a known input
+    fn parse(fields: &[&str]) -> Option<Self> {
+        let state = |word: &str| match word {
+            "pressed" | "repeated" => Some(ElementState::Pressed),
+            "released" => Some(ElementState::Released),
+            _ => None,
+        };
+        match fields {
+            ["cursor", x, y] => Some(Input::Cursor(PhysicalPosition::new(
+                x.parse().ok()?,
+                y.parse().ok()?,
+            ))),
+            ["button", name, pressed] => {
+                let (button, _) = BUTTONS.iter().find(|(_, known)| known == name)?;
+                Some(Input::Button(*button, state(pressed)?))
+            }
+            ["wheel", "line", x, y] => Some(Input::Wheel(MouseScrollDelta::LineDelta(
+                x.parse().ok()?,
+                y.parse().ok()?,
+            ))),
+            ["wheel", "pixel", x, y] => Some(Input::Wheel(MouseScrollDelta::PixelDelta(
+                PhysicalPosition::new(x.parse().ok()?, y.parse().ok()?),
+            ))),
+            ["key", pressed, kind, name] => {
+                let key = match *kind {
+                    "named" => Key::Named(
+                        *NAMED_KEYS
+                            .iter()
+                            .find(|named| format!("{named:?}") == *name)?,
+                    ),
+                    "char" => Key::Character((*name).into()),
+                    _ => return None,
+                };
+                Some(Input::Key {
+                    key,
+                    state: state(pressed)?,
+                    repeat: *pressed == "repeated",
+                })
+            }
+            ["modifiers", bits] => Some(Input::Modifiers(ModifiersState::from_bits(
+                bits.parse().ok()?,
+            )?)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = |state: &ElementState| match state {
+            ElementState::Pressed => "pressed",
+            ElementState::Released => "released",
+        };
+        match self {
+            Input::Cursor(position) => write!(f, "cursor {} {}", position.x, position.y),
+            Input::Button(button, pressed) => {
+                let name = BUTTONS
+                    .iter()
+                    .find(|(known, _)| known == button)
+                    .map_or("left", |(_, name)| name);
+                write!(f, "button {name} {}", state(pressed))
+            }
+            Input::Wheel(MouseScrollDelta::LineDelta(x, y)) => write!(f, "wheel line {x} {y}"),
+            Input::Wheel(MouseScrollDelta::PixelDelta(delta)) => {
+                write!(f, "wheel pixel {} {}", delta.x, delta.y)
+            }
+            Input::Key {
+                key,
+                state: pressed,
+                repeat,
+            } => {
+                let pressed = if *repeat { "repeated" } else { state(pressed) };
+                match key {
+                    Key::Named(named) => write!(f, "key {pressed} named {named:?}"),
+                    Key::Character(text) => write!(f, "key {pressed} char {text}"),
+                    _ => Ok(()),
+                }
+            }
+            Input::Modifiers(modifiers) => write!(f, "modifiers {}", modifiers.bits()),
+        }
+    }
+}
+
+/// Reasons an input recording couldn't be written or read
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(PathBuf, io::Error),
+    /// A line that isn't a time in milliseconds followed by an input, or goes back in time
+    Parse {
+        path: PathBuf,
+        line: usize,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(path, e) => {
+                write!(f, "Couldn't access input recording {}: {e}", path.display())
+            }
+            ReplayError::Parse { path, line } => write!(
+                f,
+                "Input recording {} line {line} should be a time in milliseconds no earlier than the line before, followed by an input",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReplayError::Io(_, e) => Some(e),
+            ReplayError::Parse { .. } => None,
+        }
+    }
+}
+
+/// Writes input to a file as it happens, one `millis input` line per event. Lines are flushed as they are written, so a crash keeps everything up to it
+pub struct Recorder {
+    file: LineWriter<File>,
+    started: Instant,
+}
+
+impl Recorder {
+    /// Creates or truncates the recording. Times are counted from `started`
+    pub fn create(path: &Path, started: Instant) -> Result<Self, ReplayError> {
+        let mut file = File::create(path)
+            .map(LineWriter::new)
+            .map_err(|e| ReplayError::Io(path.to_path_buf(), e))?;
+        writeln!(
+            file,
+            "# MineCaptcha input recording, play back with --replay"
+        )
+        .map_err(|e| ReplayError::Io(path.to_path_buf(), e))?;
+        Ok(Self { file, started })
+    }
+
+    /// Appends the input carried by `event`, if any
+    pub fn record(&mut self, event: &WindowEvent) -> io::Result<()> {
+        let Some(input) = Input::from_event(event) else {
+            return Ok(());
+        };
+        let millis = self.started.elapsed().as_millis();
+        writeln!(self.file, "{millis} {input}")
+    }
+}
+
+/// Recorded input waiting to be played back at the times it originally happened
+pub struct Replay {
+    inputs: VecDeque<(Duration, Input)>,
+    started: Instant,
+}
+
+impl Replay {
+    /// Reads a recording made with [`Recorder`]. Blank lines and lines starting with # are skipped. Times are counted from `started`
+    pub fn load(path: &Path, started: Instant) -> Result<Self, ReplayError> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| ReplayError::Io(path.to_path_buf(), e))?;
+        let inputs = parse(&contents).map_err(|line| ReplayError::Parse {
+            path: path.to_path_buf(),
+            line,
+        })?;
+        Ok(Self { inputs, started })
+    }
+
+    /// Takes every input whose time has come
+    pub fn due(&mut self, now: Instant) -> Vec<Input> {
+        let elapsed = now.saturating_duration_since(self.started);
+        let count = self
+            .inputs
+            .iter()
+            .take_while(|(at, _)| *at <= elapsed)
+            .count();
+        self.inputs.drain(..count).map(|(_, input)| input).collect()
+    }
+
+    /// When the next input should be played. None once everything was played
+    pub fn next_at(&self) -> Option<Instant> {
+        self.inputs.front().map(|(at, _)| self.started + *at)
+    }
+}
+
+/// Times and inputs of a recording. Fails with the 1-based number of the first bad line
+fn parse(contents: &str) -> Result<VecDeque<(Duration, Input)>, usize> {
+    let mut inputs = VecDeque::new();
+    let mut last = Duration::ZERO;
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        let Some((millis, fields)) = fields.split_first() else {
+            continue;
+        };
+        let at = millis.parse().ok().map(Duration::from_millis);
+        let (Some(at), Some(input)) = (at.filter(|&at| at >= last), Input::parse(fields)) else {
+            return Err(number + 1);
+        };
+        last = at;
+        inputs.push_back((at, input));
+    }
+    Ok(inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs_survive_a_round_trip() {
+        let inputs = [
+            Input::Cursor(PhysicalPosition::new(120.5, 64.0)),
+            Input::Button(MouseButton::Left, ElementState::Pressed),
+            Input::Button(MouseButton::Middle, ElementState::Released),
+            Input::Wheel(MouseScrollDelta::LineDelta(0.0, -1.5)),
+            Input::Wheel(MouseScrollDelta::PixelDelta(PhysicalPosition::new(
+                3.0, 12.25,
+            ))),
+            Input::Key {
+                key: Key::Named(NamedKey::ArrowLeft),
+                state: ElementState::Pressed,
+                repeat: true,
+            },
+            Input::Key {
+                key: Key::Character("7".into()),
+                state: ElementState::Released,
+                repeat: false,
+            },
+            Input::Modifiers(ModifiersState::SHIFT | ModifiersState::CONTROL),
+        ];
+        let recording = inputs
+            .iter()
+            .enumerate()
+            .map(|(millis, input)| format!("{millis} {input}\n"))
+            .collect::<String>();
+        let parsed = parse(&recording).expect("recording parses");
+        let expected = inputs
+            .into_iter()
+            .enumerate()
+            .map(|(millis, input)| (Duration::from_millis(millis as u64), input))
+            .collect::<VecDeque<_>>();
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn bad_lines_are_reported() {
+        assert_eq!(parse("# header\n10 cursor 1 2\n5 cursor 3 4\n"), Err(3));
+        assert_eq!(parse("10 button sideways pressed\n"), Err(1));
+        assert_eq!(parse("soon key pressed named Enter\n"), Err(1));
+    }
+
+    #[test]
+    fn inputs_are_due_in_order() {
+        let started = Instant::now();
+        let mut replay = Replay {
+            inputs: parse("0 cursor 1 1\n0 cursor 2 2\n50 cursor 3 3\n").expect("recording parses"),
+            started,
+        };
+        assert_eq!(replay.due(started).len(), 2);
+        assert_eq!(replay.next_at(), Some(started + Duration::from_millis(50)));
+        assert!(replay.due(started + Duration::from_millis(49)).is_empty());
+        assert_eq!(
+            replay.due(started + Duration::from_millis(50)),
+            [Input::Cursor(PhysicalPosition::new(3.0, 3.0))]
+        );
+        assert_eq!(replay.next_at(), None);
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::challenge::{Challenge, ChallengeSource};
+
+/// Where a challenge is in its lifecycle. Passed is final, failed and expired can be retried while attempts remain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionState {
+    /// The challenge is being generated and the window set up. The timer hasn't started
+    Loading,
+    /// The user is solving the challenge
+    Active,
+    /// An answer was handed in and is being checked
+    Submitted,
+    Passed,
+    Failed,
+    /// The time limit ran out before an answer was submitted
+    Expired,
+}
+
+impl SessionState {
+    /// Lowercase name used when reporting transitions
+    pub fn name(self) -> &'static str {
+        match self {
+            SessionState::Loading => "loading",
+            SessionState::Active => "active",
+            SessionState::Submitted => "submitted",
+            SessionState::Passed => "passed",
+            SessionState::Failed => "failed",
+            SessionState::Expired => "expired",
+        }
+    }
+}
+
+/// A state change, handed to the host so it can tell the server how the challenge went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transition {
+    pub from: SessionState,
+    pub to: SessionState,
+    /// Attempts used so far, counting submissions and expiries
+    pub attempt: u32,
+    /// Seed of the current challenge. None for challenges loaded from a file
+    pub seed: Option<u64>,
+}
+
+// One JSON object per transition so hosts reading stdout can parse it line by line
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            r#"{{"event":"transition","from":"{}","to":"{}","attempt":{}"#,
+            self.from.name(),
+            self.to.name(),
+            self.attempt
+        )?;
+        if let Some(seed) = self.seed {
+            write!(f, r#","seed":{seed}"#)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// Called on every transition. Hosts embedding the captcha pass their own, the command line client prints them
+pub type TransitionHook = Box<dyn FnMut(&Transition)>;
+
+/// Drives a challenge from loading to a verdict: starts the timer, expires it, counts attempts and decides whether a retry is allowed
+pub struct ChallengeSession {
+    state: SessionState,
+    /// When the current attempt runs out. None without a time limit or outside the active state
+    deadline: Option<Instant>,
+    attempts: u32,
+    pub max_attempts: u32,
+    on_transition: Option<TransitionHook>,
+}
+
+impl ChallengeSession {
+    pub fn new(max_attempts: u32, on_transition: Option<TransitionHook>) -> Self {
+        Self {
+            state: SessionState::Loading,
+            deadline: None,
+            attempts: 0,
+            max_attempts,
+            on_transition,
+        }
+    }
+
+    pub fn state(&self) -> SessionState {
+        self.state
+    }
+
+    /// Attempts left after the current one
+    pub fn attempts_left(&self) -> u32 {
+        self.max_attempts.saturating_sub(self.attempts)
+    }
+
+    /// Time left before the challenge expires. None without a time limit or outside the active state
+    pub fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(now))
+    }
+
+    /// Time until the whole seconds shown for the time left change, which for the last second is the deadline itself. None without a time limit or outside the active state
+    pub fn until_next_second(&self, now: Instant) -> Option<Duration> {
+        self.remaining(now).map(until_next_second)
+    }
+
+    /// Loading → Active. The timer starts here, once the challenge is on screen
+    pub fn start(&mut self, challenge: &Challenge, now: Instant) {
+        if self.state == SessionState::Loading {
+            self.deadline = challenge
+                .spec
+                .difficulty
+                .time_limit
+                .map(|limit| now + limit);
+            self.transition(SessionState::Active, challenge);
+        }
+    }
+
+    /// Active → Expired once the deadline passes. Returns true if the state changed
+    pub fn tick(&mut self, challenge: &Challenge, now: Instant) -> bool {
+        if self.state != SessionState::Active || self.remaining(now) != Some(Duration::ZERO) {
+            return false;
+        }
+        self.attempts += 1;
+        self.deadline = None;
+        self.transition(SessionState::Expired, challenge);
+        true
+    }
+
+    /// Active → Submitted → Passed or Failed, depending on whether the answer was correct
+    pub fn submit(&mut self, challenge: &Challenge, correct: bool) {
+        if self.state != SessionState::Active {
+            return;
+        }
+        self.attempts += 1;
+        self.deadline = None;
+        self.transition(SessionState::Submitted, challenge);
+        let verdict = if correct {
+            SessionState::Passed
+        } else {
+            SessionState::Failed
+        };
+        self.transition(verdict, challenge);
+    }
+
+    /// Whether a fresh challenge may be started. Only after a failure or expiry and while attempts remain
+    pub fn can_retry(&self) -> bool {
+        matches!(self.state, SessionState::Failed | SessionState::Expired)
+            && self.attempts_left() > 0
+    }
+
+    /// Failed or Expired → Loading, ready for [`ChallengeSession::start`] with the fresh challenge. Does nothing if no retry is allowed
+    pub fn begin_retry(&mut self, challenge: &Challenge) {
+        if self.can_retry() {
+            self.transition(SessionState::Loading, challenge);
+        }
+    }
+
+    fn transition(&mut self, to: SessionState, challenge: &Challenge) {
+        let transition = Transition {
+            from: self.state,
+            to,
+            attempt: self.attempts,
+            seed: match challenge.spec.source {
+                ChallengeSource::Seed(seed) => Some(seed),
+                ChallengeSource::File(_) => None,
+            },
+        };
+        log::info!("Challenge {} -> {}", transition.from.name(), to.name());
+        self.state = to;
+        if let Some(on_transition) = self.on_transition.as_mut() {
+            on_transition(&transition);
+        }
+    }
+}
+
+/// Time until the seconds shown for `remaining`, rounded up, count down. A whole number of seconds stays on screen for a full second
+fn until_next_second(remaining: Duration) -> Duration {
+    match remaining.subsec_nanos() {
+        0 => remaining.min(Duration::from_secs(1)),
+        nanos => Duration::from_nanos(nanos.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_second_is_never_immediate_while_time_is_left() {
+        let cases = [
+            (Duration::from_millis(5_250), Duration::from_millis(250)),
+            (Duration::from_secs(5), Duration::from_secs(1)),
+            (Duration::from_millis(400), Duration::from_millis(400)),
+            (Duration::from_nanos(1), Duration::from_nanos(1)),
+            (Duration::ZERO, Duration::ZERO),
+        ];
+        for (remaining, expected) in cases {
+            assert_eq!(until_next_second(remaining), expected, "{remaining:?}");
+        }
+    }
+}
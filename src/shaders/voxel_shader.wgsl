@@ -1,8 +1,7 @@
-// Define camera parameters
+// Define camera parameters. Everything is in grid space, where one unit is one voxel and the grid spans 0 to GRID_SIZE on each axis
 struct Camera {
-    position: vec3<f32>,   // Camera position in world space
-    direction: vec3<f32>,  // Ray direction
-    invResolution: vec2<f32>, // Inverse screen resolution
+    inverseViewProjection: mat4x4<f32>, // Clip space back to grid space
+    position: vec3<f32>,                // Camera position in grid space
 };
 
 // Define camera parameters
@@ -25,12 +24,50 @@ struct VoxelGrid {
     position: vec3<f32>,   // Voxel Grid position in world space
 };
 
-struct RayMarchingSystem {
-    camera: Camera,
-    voxelGrid: VoxelGrid,
-    screen: Screen,
+// Surface size in pixels. Updated from Rust whenever the window is resized
+@group(0) @binding(0)
+var<uniform> screen: Screen;
+
+// Updated from Rust before every frame
+@group(0) @binding(1)
+var<uniform> camera: Camera;
+
+@group(0) @binding(2)
+var<uniform> voxelGrid: VoxelGrid;
+
+// Values of Cursor.ghostState
+const GHOST_NONE: u32 = 0u;
+const GHOST_ALLOWED: u32 = 1u;
+const GHOST_FORBIDDEN: u32 = 2u;
+
+// Where the pointer is. Drawn as a translucent ghost cube where a block would go and an outline around the hovered face
+struct Cursor {
+    ghostCell: vec3<i32>,    // Cell the ghost is drawn in, may be outside the grid
+    ghostState: u32,         // One of the GHOST_ constants
+    ghostColor: vec3<f32>,   // Color of an allowed ghost
+    hovered: u32,            // Whether hoveredCell and hoveredNormal point at a voxel face (1) or not (0)
+    hoveredCell: vec3<i32>,
+    hoveredNormal: vec3<i32>,
+};
+
+// Updated from Rust before every frame. Left zeroed for views without a pointer such as the target preview
+@group(0) @binding(3)
+var<uniform> cursor: Cursor;
+
+// Number of entries in Palette, matching PALETTE in challenge.rs
+const PALETTE_SIZE: u32 = 6u;
+
+// How block colors are drawn. Voxels keep the standard palette colors, which are looked up in sources and swapped for the matching entry of colors
+struct Palette {
+    sources: array<vec4<f32>, PALETTE_SIZE>,
+    colors: array<vec4<f32>, PALETTE_SIZE>,
+    patterns: u32,       // Whether each palette color gets its own pattern (1) or not (0)
 };
 
+// Updated from Rust before every frame, following the preset picked in the controls window
+@group(0) @binding(4)
+var<uniform> palette: Palette;
+
 fn getVoxelIndex(x: u32, y: u32, z: u32) -> u32 {
     return x + y * GRID_SIZE + z * GRID_SIZE * GRID_SIZE;
 }
@@ -57,21 +94,235 @@ fn vs_main(
     return out;
 }
 
+// Result of marching a ray through the grid
+struct Hit {
+    hit: bool,
+    cell: vec3<i32>,     // Voxel that was hit
+    normal: vec3<f32>,   // Normal of the face the ray entered through
+    distance: f32,       // Distance along the ray to that face
+};
+
+// Slab test against an axis aligned box. Returns the entry and exit distances, entry > exit means a miss
+fn intersectBox(origin: vec3<f32>, direction: vec3<f32>, low: vec3<f32>, high: vec3<f32>) -> vec2<f32> {
+    let inverse = 1.0 / direction;
+    let t0 = (low - origin) * inverse;
+    let t1 = (high - origin) * inverse;
+    let near = min(t0, t1);
+    let far = max(t0, t1);
+    let entry = max(max(near.x, near.y), max(near.z, 0.0));
+    let exit = min(min(far.x, far.y), far.z);
+    return vec2<f32>(entry, exit);
+}
+
+fn intersectGrid(origin: vec3<f32>, direction: vec3<f32>) -> vec2<f32> {
+    return intersectBox(origin, direction, vec3<f32>(0.0), vec3<f32>(f32(GRID_SIZE)));
+}
+
+// Distance from a point on the surface of a unit cube (in cell local coordinates) to the nearest edge of the face it lies on.
+// One component is close to 0 or 1 because the point is on a face, so the distance to the face's edges is the middle of the three
+fn edgeDistance(local: vec3<f32>) -> f32 {
+    let e = min(local, vec3<f32>(1.0) - local);
+    return max(min(e.x, e.y), min(max(e.x, e.y), e.z));
+}
+
+// Composites a translucent color over another one
+fn over(top: vec4<f32>, bottom: vec4<f32>) -> vec4<f32> {
+    let alpha = top.a + bottom.a * (1.0 - top.a);
+    let color = (top.rgb * top.a + bottom.rgb * bottom.a * (1.0 - top.a)) / max(alpha, 1e-5);
+    return vec4<f32>(color, alpha);
+}
+
+// Index of a standard palette color, or PALETTE_SIZE for colors outside the palette
+fn paletteIndex(color: vec3<f32>) -> u32 {
+    for (var i = 0u; i < PALETTE_SIZE; i++) {
+        if distance(color, palette.sources[i].rgb) < 0.01 {
+            return i;
+        }
+    }
+    return PALETTE_SIZE;
+}
+
+// The color drawn for a stored voxel color under the current preset
+fn displayColor(color: vec3<f32>) -> vec3<f32> {
+    let index = paletteIndex(color);
+    if index == PALETTE_SIZE {
+        return color;
+    }
+    return palette.colors[index].rgb;
+}
+
+// Coverage of the pattern for palette entry index at uv on a face, from 0 to 1. Every entry gets a different shape so colors can be told apart without seeing hue:
+// horizontal stripes, dots, diagonal stripes, a cross glyph, none and a ring glyph
+fn patternMask(index: u32, uv: vec2<f32>) -> f32 {
+    let aa = 0.04;
+    switch index {
+        case 0u: {
+            let band = abs(fract(uv.y * 3.0) - 0.5);
+            return smoothstep(0.25 + aa, 0.25 - aa, band);
+        }
+        case 1u: {
+            let cell = fract(uv * 3.0) - vec2<f32>(0.5);
+            return smoothstep(0.25 + aa, 0.25 - aa, length(cell));
+        }
+        case 2u: {
+            let band = abs(fract((uv.x + uv.y) * 2.5) - 0.5);
+            return smoothstep(0.2 + aa, 0.2 - aa, band);
+        }
+        case 3u: {
+            let centered = abs(uv - vec2<f32>(0.5));
+            let arm = min(abs(centered.x - centered.y), 1.0);
+            let inside = max(centered.x, centered.y) < 0.32;
+            return select(0.0, smoothstep(0.07 + aa, 0.07 - aa, arm), inside);
+        }
+        case 5u: {
+            let ring = abs(length(uv - vec2<f32>(0.5)) - 0.25);
+            return smoothstep(0.06 + aa, 0.06 - aa, ring);
+        }
+        default: {
+            return 0.0;
+        }
+    }
+}
+
+// Position on the face a point lies on, from 0 to 1 across the face, given the face normal
+fn faceUv(local: vec3<f32>, normal: vec3<f32>) -> vec2<f32> {
+    if abs(normal.x) > 0.5 {
+        return local.zy;
+    }
+    if abs(normal.y) > 0.5 {
+        return local.xz;
+    }
+    return local.xy;
+}
+
+// Amanatides and Woo voxel traversal. Steps from cell to cell along the ray until a solid voxel is found or the ray leaves the grid
+fn marchGrid(origin: vec3<f32>, direction: vec3<f32>) -> Hit {
+    var result = Hit(false, vec3<i32>(0), vec3<f32>(0.0), 0.0);
+    let span = intersectGrid(origin, direction);
+    if span.x > span.y {
+        return result;
+    }
+
+    // Nudge the start inside the grid so floor() lands on the first cell
+    let start = origin + direction * (span.x + 1e-4);
+    var cell = clamp(vec3<i32>(floor(start)), vec3<i32>(0), vec3<i32>(i32(GRID_SIZE) - 1));
+    let step = vec3<i32>(sign(direction));
+    let delta = abs(1.0 / direction);
+    // Distance along the ray to the next cell boundary on each axis
+    var side = (sign(direction) * (vec3<f32>(cell) - start) + sign(direction) * 0.5 + 0.5) * delta;
+    // The face we entered the grid through is the one whose slab was crossed last
+    let t0 = (vec3<f32>(0.0) - origin) / direction;
+    let t1 = (vec3<f32>(f32(GRID_SIZE)) - origin) / direction;
+    let near = min(t0, t1);
+    var normal = vec3<f32>(0.0);
+    if near.x >= near.y && near.x >= near.z {
+        normal = vec3<f32>(-sign(direction.x), 0.0, 0.0);
+    } else if near.y >= near.z {
+        normal = vec3<f32>(0.0, -sign(direction.y), 0.0);
+    } else {
+        normal = vec3<f32>(0.0, 0.0, -sign(direction.z));
+    }
+    var distance = span.x;
+
+    // A ray crosses at most 3 * GRID_SIZE cells
+    for (var i = 0u; i < 3u * GRID_SIZE; i++) {
+        if any(cell < vec3<i32>(0)) || any(cell >= vec3<i32>(i32(GRID_SIZE))) {
+            break;
+        }
+        let voxel = voxelGrid.voxels[getVoxelIndex(u32(cell.x), u32(cell.y), u32(cell.z))];
+        if voxel.isSolid != 0u {
+            result.hit = true;
+            result.cell = cell;
+            result.normal = normal;
+            result.distance = distance;
+            return result;
+        }
+        // Advance along whichever axis reaches its next boundary first
+        if side.x < side.y && side.x < side.z {
+            distance = span.x + side.x;
+            side.x += delta.x;
+            cell.x += step.x;
+            normal = vec3<f32>(-f32(step.x), 0.0, 0.0);
+        } else if side.y < side.z {
+            distance = span.x + side.y;
+            side.y += delta.y;
+            cell.y += step.y;
+            normal = vec3<f32>(0.0, -f32(step.y), 0.0);
+        } else {
+            distance = span.x + side.z;
+            side.z += delta.z;
+            cell.z += step.z;
+            normal = vec3<f32>(0.0, 0.0, -f32(step.z));
+        }
+    }
+    return result;
+}
+
 @fragment
 fn fs_main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
-    // Passed in vector has x and y pixel positions of input
-    let normalized_coord = frag_coord.xy / vec2<f32>(100.0, 100.0);  // Adjust these values to your viewport size
+    // Passed in vector has x and y pixel positions of input. Pixel y grows downwards while clip space y grows upwards
+    let normalized_coord = frag_coord.xy / vec2<f32>(screen.width, screen.height);
+    let clip = vec2<f32>(normalized_coord.x * 2.0 - 1.0, 1.0 - normalized_coord.y * 2.0);
+
+    // Unproject a point on the far plane to get the ray direction in grid space
+    let far_point = camera.inverseViewProjection * vec4<f32>(clip, 1.0, 1.0);
+    let origin = camera.position;
+    let direction = normalize(far_point.xyz / far_point.w - origin);
 
-    if frag_coord[1] > 100 {
+    let hit = marchGrid(origin, direction);
+    var color = vec4<f32>(0.0);
+    // Distance to the first opaque surface, so the ghost is hidden behind blocks
+    var depth = 3.4e38;
+    if hit.hit {
+        let voxel = voxelGrid.voxels[getVoxelIndex(u32(hit.cell.x), u32(hit.cell.y), u32(hit.cell.z))];
+        // Fixed light from above and in front so each face of a cube gets a distinct shade
+        let light = normalize(vec3<f32>(0.4, 1.0, 0.7));
+        let diffuse = max(dot(hit.normal, light), 0.0);
+        let local = origin + direction * hit.distance - vec3<f32>(hit.cell);
+        var base = displayColor(voxel.color);
+        // Patterns are drawn in black or white, whichever stands out more from the block
+        let index = paletteIndex(voxel.color);
+        if palette.patterns != 0u && index < PALETTE_SIZE {
+            let luminance = dot(base, vec3<f32>(0.299, 0.587, 0.114));
+            let ink = select(vec3<f32>(1.0), vec3<f32>(0.05), luminance > 0.5);
+            base = mix(base, ink, 0.6 * patternMask(index, faceUv(local, hit.normal)));
+        }
+        var shaded = base * (0.35 + 0.65 * diffuse);
+        // Brighten the hovered face and outline its edges
+        if cursor.hovered != 0u && all(hit.cell == cursor.hoveredCell) && all(vec3<i32>(hit.normal) == cursor.hoveredNormal) {
+            shaded = mix(shaded, vec3<f32>(1.0), select(0.15, 0.9, edgeDistance(local) < 0.06));
+        }
+        color = vec4<f32>(shaded, 1.0);
+        depth = hit.distance;
+    } else if direction.y < 0.0 {
+        // Draw the grid floor as a faint checkerboard so empty space still shows where blocks can go
+        let t = -origin.y / direction.y;
+        let floor_point = origin + direction * t;
+        if all(floor_point.xz >= vec2<f32>(0.0)) && all(floor_point.xz <= vec2<f32>(f32(GRID_SIZE))) {
+            let checker = (i32(floor(floor_point.x)) + i32(floor(floor_point.z))) & 1;
+            color = vec4<f32>(vec3<f32>(1.0), 0.08 + 0.06 * f32(checker));
+        }
+    }
+
+    // Translucent ghost where the next block would go, red when it can't go there
+    if cursor.ghostState != GHOST_NONE {
+        let low = vec3<f32>(cursor.ghostCell);
+        let span = intersectBox(origin, direction, low, low + vec3<f32>(1.0));
+        // The keyboard cursor can sit on a solid block, so the ghost also shows when it touches the surface in front
+        if span.x <= span.y && span.x < depth + 1e-3 {
+            var ghostColor = displayColor(cursor.ghostColor);
+            if cursor.ghostState == GHOST_FORBIDDEN {
+                ghostColor = vec3<f32>(0.9, 0.15, 0.15);
+            }
+            let local = origin + direction * span.x - low;
+            let alpha = select(0.4, 0.85, edgeDistance(local) < 0.04);
+            color = over(vec4<f32>(ghostColor, alpha), color);
+        }
+    }
+
+    // Nothing hit, let the background color show through
+    if color.a <= 0.0 {
         discard;
     }
-    
-    // Use the normalized coordinates for the red and green channels
-    // Add some variation to make it more interesting
-    return vec4<f32>(
-        normalized_coord.xy,
-        0.,
-        1.
-    );
+    return color;
 }
-
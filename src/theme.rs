@@ -0,0 +1,209 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use egui::{
+    Color32, ColorImage, Context, FontData, FontDefinitions, FontFamily, Rgba, Rounding, Shadow,
+    TextStyle, TextureHandle, TextureOptions, Visuals,
+};
+
+/// Name the theme's font is registered under in egui
+const THEME_FONT: &str = "theme";
+
+/// Look of the window: the style of the controls and the color behind the grid. Loaded from a file with `--theme`, or built directly by hosts embedding the captcha so it matches their site
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// Dark or light base style for the controls
+    pub mode: egui::Theme,
+    /// Color of selections and links. The base style's blue if None
+    pub accent: Option<Color32>,
+    /// Color behind the voxel grid in every view. Linear, like the clear color it becomes
+    pub background: Rgba,
+    /// Corner radius of windows, menus and widgets in points
+    pub rounding: f32,
+    /// TrueType or OpenType font tried before egui's built in fonts
+    pub font: Option<Vec<u8>>,
+    /// Size of body text in points. Headings and small text are scaled along with it
+    pub font_size: Option<f32>,
+    /// Image shown at the top of the controls window
+    pub logo: Option<ColorImage>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            mode: egui::Theme::Dark,
+            accent: None,
+            background: Rgba::from_rgb(0.17, 0.60, 0.88),
+            rounding: 2.0,
+            font: None,
+            font_size: None,
+            logo: None,
+        }
+    }
+}
+
+/// Reasons a theme file couldn't be loaded
+#[derive(Debug)]
+pub enum ThemeError {
+    /// The theme file or the font it names couldn't be read
+    Io(PathBuf, io::Error),
+    /// A line that isn't a known `key = value` pair with a valid value
+    Parse { path: PathBuf, line: usize },
+    /// The logo isn't a readable PNG
+    Logo(PathBuf, image::ImageError),
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThemeError::Io(path, e) => write!(f, "Couldn't read {}: {e}", path.display()),
+            ThemeError::Parse { path, line } => write!(
+                f,
+                "Theme {} line {line} should be \"key = value\" with key one of mode, accent, background, rounding, font, font-size or logo",
+                path.display()
+            ),
+            ThemeError::Logo(path, e) => {
+                write!(f, "Couldn't load logo {}: {e}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ThemeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ThemeError::Io(_, e) => Some(e),
+            ThemeError::Logo(_, e) => Some(e),
+            ThemeError::Parse { .. } => None,
+        }
+    }
+}
+
+impl Theme {
+    /// Reads a theme file. Each line is `key = value` and lines starting with `#` are comments. Colors are `#rrggbb`, and font and logo paths are relative to the file. Keys that are left out keep their default
+    pub fn load(path: &Path) -> Result<Self, ThemeError> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| ThemeError::Io(path.to_path_buf(), e))?;
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let mut theme = Theme::default();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parse_error = || ThemeError::Parse {
+                path: path.to_path_buf(),
+                line: number + 1,
+            };
+            let (key, value) = line.split_once('=').ok_or_else(parse_error)?;
+            let value = value.trim();
+            match key.trim() {
+                "mode" => {
+                    theme.mode = match value {
+                        "dark" => egui::Theme::Dark,
+                        "light" => egui::Theme::Light,
+                        _ => return Err(parse_error()),
+                    }
+                }
+                "accent" => {
+                    theme.accent = Some(Color32::from_hex(value).map_err(|_| parse_error())?)
+                }
+                "background" => {
+                    theme.background = Color32::from_hex(value).map_err(|_| parse_error())?.into()
+                }
+                "rounding" => {
+                    theme.rounding = value
+                        .parse()
+                        .ok()
+                        .filter(|&rounding: &f32| rounding >= 0.0)
+                        .ok_or_else(parse_error)?
+                }
+                "font" => {
+                    let font = directory.join(value);
+                    theme.font = Some(fs::read(&font).map_err(|e| ThemeError::Io(font, e))?);
+                }
+                "font-size" => {
+                    theme.font_size = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|&size: &f32| size > 0.0)
+                            .ok_or_else(parse_error)?,
+                    )
+                }
+                "logo" => {
+                    let logo = directory.join(value);
+                    let image = image::open(&logo)
+                        .map_err(|e| ThemeError::Logo(logo, e))?
+                        .to_rgba8();
+                    let size = [image.width() as usize, image.height() as usize];
+                    theme.logo = Some(ColorImage::from_rgba_unmultiplied(size, &image));
+                }
+                _ => return Err(parse_error()),
+            }
+        }
+        Ok(theme)
+    }
+
+    /// Color the scene and the target preview are cleared to
+    pub fn clear_color(&self) -> wgpu::Color {
+        let [r, g, b, a] = self.background.to_array().map(f64::from);
+        wgpu::Color { r, g, b, a }
+    }
+
+    /// Sets egui's style and fonts to the theme and uploads the logo. Safe to call again on a context that already has the theme, which happens when the GPU state is rebuilt
+    pub fn apply(&self, ctx: &Context) -> Option<TextureHandle> {
+        // Pinning the mode stops egui from following the system's dark or light setting
+        ctx.set_theme(self.mode);
+        let mut visuals = match self.mode {
+            egui::Theme::Dark => Visuals::dark(),
+            egui::Theme::Light => Visuals::light(),
+        };
+        let rounding = Rounding::same(self.rounding);
+        visuals.window_rounding = rounding;
+        visuals.menu_rounding = rounding;
+        for widget in [
+            &mut visuals.widgets.noninteractive,
+            &mut visuals.widgets.inactive,
+            &mut visuals.widgets.hovered,
+            &mut visuals.widgets.active,
+            &mut visuals.widgets.open,
+        ] {
+            widget.rounding = rounding;
+        }
+        visuals.window_shadow = Shadow::NONE;
+        if let Some(accent) = self.accent {
+            visuals.selection.bg_fill = accent;
+            visuals.hyperlink_color = accent;
+        }
+        ctx.set_visuals(visuals);
+
+        if let Some(font) = &self.font {
+            let mut fonts = FontDefinitions::default();
+            fonts
+                .font_data
+                .insert(THEME_FONT.to_owned(), FontData::from_owned(font.clone()));
+            fonts
+                .families
+                .entry(FontFamily::Proportional)
+                .or_default()
+                .insert(0, THEME_FONT.to_owned());
+            ctx.set_fonts(fonts);
+        }
+        if let Some(size) = self.font_size {
+            // Scaling every style by the body's ratio keeps headings and small text in proportion. Once applied the ratio is 1, so applying again changes nothing
+            ctx.style_mut(|style| {
+                let scale = size / style.text_styles[&TextStyle::Body].size;
+                for font in style.text_styles.values_mut() {
+                    font.size *= scale;
+                }
+            });
+        }
+
+        self.logo
+            .as_ref()
+            .map(|logo| ctx.load_texture("logo", logo.clone(), TextureOptions::LINEAR))
+    }
+}
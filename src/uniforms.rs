@@ -0,0 +1,413 @@
+use bytemuck::{Pod, Zeroable};
+use glam::{DQuat, DVec3, IVec3, Mat4, Vec3};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupLayout, Buffer, Device, Queue,
+};
+
+use crate::{
+    camera::Camera, challenge::PALETTE, palette::PalettePreset, picking::Pick, voxel::VoxelGrid,
+};
+
+/// Surface size in pixels. Mirrors the `Screen` struct in the voxel shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct ScreenUniform {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl ScreenUniform {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width: width as f32,
+            height: height as f32,
+        }
+    }
+}
+
+/// What the raymarcher needs from the camera. Mirrors the `Camera` struct in the voxel shader. Everything is expressed in grid space so the shader never deals with the grid's rotation
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct CameraUniform {
+    /// Clip space back to grid space
+    pub inverse_view_projection: Mat4,
+    /// Camera position in grid space
+    pub position: Vec3,
+    /// The vec3 above is padded to 16 bytes in WGSL
+    pub _padding: f32,
+}
+
+impl CameraUniform {
+    /// Builds the uniform for the camera looking at `grid` turned by `rotation`
+    pub fn new(camera: &mut Camera, grid: &VoxelGrid, rotation: DQuat) -> Self {
+        let view_model = camera.view_matrix() * grid.model_matrix(rotation);
+        let view_projection = camera.projection.matrix() * view_model;
+        Self {
+            inverse_view_projection: view_projection.inverse().as_mat4(),
+            position: view_model.inverse().transform_point3(DVec3::ZERO).as_vec3(),
+            _padding: 0.0,
+        }
+    }
+}
+
+/// Placement ghost and hovered face. Mirrors the `Cursor` struct in the voxel shader. All zeroes shows neither
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct CursorUniform {
+    /// Cell the ghost cube is drawn in. May lie outside the grid
+    pub ghost_cell: IVec3,
+    /// One of the `GHOST_*` constants
+    pub ghost_state: u32,
+    /// Color of an allowed ghost, normally the block that would be placed
+    pub ghost_color: Vec3,
+    /// Whether `hovered_cell` and `hovered_normal` point at a voxel face (1) or not (0)
+    pub hovered: u32,
+    pub hovered_cell: IVec3,
+    /// The vec3s are each padded to 16 bytes in WGSL
+    pub _padding0: u32,
+    pub hovered_normal: IVec3,
+    pub _padding1: u32,
+}
+
+impl CursorUniform {
+    pub const GHOST_NONE: u32 = 0;
+    pub const GHOST_ALLOWED: u32 = 1;
+    pub const GHOST_FORBIDDEN: u32 = 2;
+
+    /// Builds the uniform for the pick under the pointer. `allowed` is whether a block may go into the pick's placement cell
+    pub fn new(pick: Option<Pick>, allowed: bool, ghost_color: Vec3) -> Self {
+        let Some(pick) = pick else {
+            return Self {
+                ghost_state: Self::GHOST_NONE,
+                ..Self::zeroed()
+            };
+        };
+        Self {
+            ghost_cell: pick.placement(),
+            ghost_state: if allowed {
+                Self::GHOST_ALLOWED
+            } else {
+                Self::GHOST_FORBIDDEN
+            },
+            ghost_color,
+            hovered: pick.solid as u32,
+            hovered_cell: pick.cell,
+            _padding0: 0,
+            hovered_normal: pick.normal,
+            _padding1: 0,
+        }
+    }
+
+    /// Ghost around a single cell, for the keyboard cursor. Nothing is hovered
+    pub fn cell(cell: IVec3, allowed: bool, ghost_color: Vec3) -> Self {
+        Self {
+            ghost_cell: cell,
+            ghost_state: if allowed {
+                Self::GHOST_ALLOWED
+            } else {
+                Self::GHOST_FORBIDDEN
+            },
+            ghost_color,
+            ..Self::zeroed()
+        }
+    }
+}
+
+/// How block colors are drawn. Mirrors the `Palette` struct in the voxel shader, which looks up each voxel's color in `sources` and draws the matching entry of `colors` instead
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct PaletteUniform {
+    /// The [`PALETTE`] colors voxels are stored with. Arrays in uniforms have a 16 byte stride, so each color takes a vec4
+    pub sources: [[f32; 4]; 6],
+    pub colors: [[f32; 4]; 6],
+    /// Whether each palette color gets its own pattern (1) or not (0)
+    pub patterns: u32,
+    /// WGSL rounds the struct size up to a multiple of 16
+    pub _padding: [u32; 3],
+}
+
+impl PaletteUniform {
+    pub fn new(preset: PalettePreset, patterns: bool) -> Self {
+        Self {
+            sources: PALETTE.map(|color| color.extend(1.0).to_array()),
+            colors: preset.colors().map(|color| color.extend(1.0).to_array()),
+            patterns: patterns as u32,
+            _padding: [0; 3],
+        }
+    }
+}
+
+/// The buffers and bind group behind one raymarched view. The main scene and the target preview each own one so they can show different grids from different cameras with the same pipeline
+pub struct UniformBindings {
+    /// The `Screen` struct in the voxel shader
+    screen_buffer: Buffer,
+    /// The `Camera` struct in the voxel shader
+    camera_buffer: Buffer,
+    /// The `VoxelGrid` struct in the voxel shader
+    grid_buffer: Buffer,
+    /// The `Cursor` struct in the voxel shader. Left zeroed for views without a pointer
+    cursor_buffer: Buffer,
+    /// The `Palette` struct in the voxel shader
+    palette_buffer: Buffer,
+    /// Bind group for every uniform the voxel shader reads
+    pub bind_group: BindGroup,
+}
+
+impl UniformBindings {
+    /// Layout shared by every view. Each binding connects to @group(0) @binding(n) in the shader
+    pub fn layout(device: &Device) -> BindGroupLayout {
+        let uniform_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Voxel Bind Group Layout"),
+            entries: &[
+                uniform_entry(0),
+                uniform_entry(1),
+                uniform_entry(2),
+                uniform_entry(3),
+                uniform_entry(4),
+            ],
+        })
+    }
+
+    /// Creates the buffers for a view `screen` pixels large showing `grid`. The camera is written before every frame
+    pub fn new(
+        device: &Device,
+        layout: &BindGroupLayout,
+        label: &str,
+        screen: ScreenUniform,
+        grid: &VoxelGrid,
+    ) -> Self {
+        let buffer = |name: &str, contents: &[u8]| {
+            device.create_buffer_init(&BufferInitDescriptor {
+                label: Some(&format!("{label} {name} Uniform Buffer")),
+                contents,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            })
+        };
+        let screen_buffer = buffer("Screen", bytemuck::bytes_of(&screen));
+        let camera_buffer = buffer("Camera", bytemuck::bytes_of(&CameraUniform::zeroed()));
+        let grid_buffer = buffer("Voxel Grid", bytemuck::bytes_of(grid));
+        let cursor_buffer = buffer("Cursor", bytemuck::bytes_of(&CursorUniform::zeroed()));
+        let palette = PaletteUniform::new(PalettePreset::default(), false);
+        let palette_buffer = buffer("Palette", bytemuck::bytes_of(&palette));
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{label} Bind Group")),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: screen_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: grid_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: cursor_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: palette_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        Self {
+            screen_buffer,
+            camera_buffer,
+            grid_buffer,
+            cursor_buffer,
+            palette_buffer,
+            bind_group,
+        }
+    }
+
+    pub fn write_screen(&self, queue: &Queue, screen: ScreenUniform) {
+        queue.write_buffer(&self.screen_buffer, 0, bytemuck::bytes_of(&screen));
+    }
+
+    pub fn write_camera(&self, queue: &Queue, camera: CameraUniform) {
+        queue.write_buffer(&self.camera_buffer, 0, bytemuck::bytes_of(&camera));
+    }
+
+    pub fn write_grid(&self, queue: &Queue, grid: &VoxelGrid) {
+        queue.write_buffer(&self.grid_buffer, 0, bytemuck::bytes_of(grid));
+    }
+
+    pub fn write_cursor(&self, queue: &Queue, cursor: CursorUniform) {
+        queue.write_buffer(&self.cursor_buffer, 0, bytemuck::bytes_of(&cursor));
+    }
+
+    pub fn write_palette(&self, queue: &Queue, palette: PaletteUniform) {
+        queue.write_buffer(&self.palette_buffer, 0, bytemuck::bytes_of(&palette));
+    }
+}
+
+/// The voxel shader is otherwise only checked by the driver when the window starts. These parse and validate it without a GPU and check that every struct uploaded to it has the layout the shader expects, since a missed vec3 padding shifts every field after it
+#[cfg(test)]
+mod tests {
+    use std::mem::{offset_of, size_of};
+
+    use naga::{
+        front::wgsl,
+        proc::Layouter,
+        valid::{Capabilities, ValidationFlags, Validator},
+        Expression, Literal, Module, TypeInner,
+    };
+
+    use super::*;
+    use crate::voxel::{Voxel, GRID_SIZE, TOTAL_VOXELS};
+
+    const SHADER: &str = include_str!("shaders/voxel_shader.wgsl");
+
+    fn module() -> Module {
+        wgsl::parse_str(SHADER).unwrap_or_else(|e| panic!("{}", e.emit_to_string(SHADER)))
+    }
+
+    /// Checks the shader struct called `name` against the Rust type `T`. `members` pairs each shader member with the offset of the matching Rust field
+    fn assert_layout<T>(module: &Module, name: &str, members: &[(&str, usize)]) {
+        let mut layouter = Layouter::default();
+        layouter.update(module.to_ctx()).expect("layout");
+        let (handle, ty) = module
+            .types
+            .iter()
+            .find(|(_, ty)| ty.name.as_deref() == Some(name))
+            .unwrap_or_else(|| panic!("the shader has no struct {name}"));
+        let TypeInner::Struct {
+            members: shader_members,
+            span,
+        } = &ty.inner
+        else {
+            panic!("{name} isn't a struct");
+        };
+        let layout = layouter[handle];
+        assert_eq!(*span as usize, size_of::<T>(), "size of {name}");
+        // Arrays and buffers of the struct are only laid out the same if the Rust size keeps the shader's alignment
+        assert_eq!(
+            layout.alignment.round_up(*span),
+            *span,
+            "{name} isn't padded to its alignment"
+        );
+        assert_eq!(
+            shader_members.len(),
+            members.len(),
+            "members of {name} changed"
+        );
+        for (member, &(member_name, offset)) in shader_members.iter().zip(members) {
+            assert_eq!(
+                member.name.as_deref(),
+                Some(member_name),
+                "member of {name}"
+            );
+            assert_eq!(
+                member.offset as usize, offset,
+                "offset of {name}.{member_name}"
+            );
+        }
+    }
+
+    /// Value of an integer `const` declared in the shader
+    fn constant(module: &Module, name: &str) -> usize {
+        let (_, constant) = module
+            .constants
+            .iter()
+            .find(|(_, constant)| constant.name.as_deref() == Some(name))
+            .unwrap_or_else(|| panic!("the shader has no constant {name}"));
+        match module.global_expressions[constant.init] {
+            Expression::Literal(Literal::U32(value)) => value as usize,
+            Expression::Literal(Literal::I32(value)) => value as usize,
+            ref other => panic!("{name} is {other:?}"),
+        }
+    }
+
+    #[test]
+    fn shader_validates() {
+        let module = module();
+        Validator::new(ValidationFlags::all(), Capabilities::default())
+            .validate(&module)
+            .unwrap_or_else(|e| panic!("{}", e.emit_to_string(SHADER)));
+    }
+
+    #[test]
+    fn constants_match() {
+        let module = module();
+        assert_eq!(constant(&module, "GRID_SIZE"), GRID_SIZE);
+        assert_eq!(constant(&module, "TOTAL_VOXELS"), TOTAL_VOXELS);
+        assert_eq!(constant(&module, "PALETTE_SIZE"), PALETTE.len());
+    }
+
+    #[test]
+    fn struct_layouts_match() {
+        let module = module();
+        assert_layout::<Voxel>(
+            &module,
+            "Voxel",
+            &[
+                ("color", offset_of!(Voxel, color)),
+                ("isSolid", offset_of!(Voxel, is_solid)),
+            ],
+        );
+        assert_layout::<VoxelGrid>(
+            &module,
+            "VoxelGrid",
+            &[
+                ("voxels", offset_of!(VoxelGrid, voxels)),
+                ("position", offset_of!(VoxelGrid, position)),
+            ],
+        );
+        assert_layout::<CameraUniform>(
+            &module,
+            "Camera",
+            &[
+                (
+                    "inverseViewProjection",
+                    offset_of!(CameraUniform, inverse_view_projection),
+                ),
+                ("position", offset_of!(CameraUniform, position)),
+            ],
+        );
+        assert_layout::<ScreenUniform>(
+            &module,
+            "Screen",
+            &[
+                ("width", offset_of!(ScreenUniform, width)),
+                ("height", offset_of!(ScreenUniform, height)),
+            ],
+        );
+        assert_layout::<CursorUniform>(
+            &module,
+            "Cursor",
+            &[
+                ("ghostCell", offset_of!(CursorUniform, ghost_cell)),
+                ("ghostState", offset_of!(CursorUniform, ghost_state)),
+                ("ghostColor", offset_of!(CursorUniform, ghost_color)),
+                ("hovered", offset_of!(CursorUniform, hovered)),
+                ("hoveredCell", offset_of!(CursorUniform, hovered_cell)),
+                ("hoveredNormal", offset_of!(CursorUniform, hovered_normal)),
+            ],
+        );
+        assert_layout::<PaletteUniform>(
+            &module,
+            "Palette",
+            &[
+                ("sources", offset_of!(PaletteUniform, sources)),
+                ("colors", offset_of!(PaletteUniform, colors)),
+                ("patterns", offset_of!(PaletteUniform, patterns)),
+            ],
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use bytemuck::{Pod, Zeroable};
+use glam::{DMat4, DQuat, DVec3, Vec3};
+
+/// Number of voxels along each edge of the grid. Must match `GRID_SIZE` in the voxel shader
+pub const GRID_SIZE: usize = 8;
+/// Total number of voxels in the grid. Must match `TOTAL_VOXELS` in the voxel shader
+pub const TOTAL_VOXELS: usize = GRID_SIZE * GRID_SIZE * GRID_SIZE;
+
+/// Width of the whole grid in world units. The camera sits one unit from the origin so this keeps the grid comfortably in view
+pub const GRID_WORLD_SIZE: f64 = 0.5;
+
+/// A single cell of the voxel grid. Mirrors the `Voxel` struct in the voxel shader and is uploaded as is
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Pod, Zeroable)]
+pub struct Voxel {
+    /// Voxel color or material
+    pub color: Vec3,
+    /// Whether this voxel is solid (1) or empty (0)
+    pub is_solid: u32,
+}
+
+impl Voxel {
+    pub const EMPTY: Voxel = Voxel {
+        color: Vec3::ZERO,
+        is_solid: 0,
+    };
+
+    pub fn solid(color: Vec3) -> Self {
+        Self { color, is_solid: 1 }
+    }
+
+    pub fn is_solid(&self) -> bool {
+        self.is_solid != 0
+    }
+}
+
+/// CPU side copy of the voxel grid. This is the source of truth for the puzzle, the GPU buffers are rebuilt from it whenever needed. The layout mirrors the `VoxelGrid` struct in the voxel shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct VoxelGrid {
+    /// Voxels stored x first, then y, then z. See [`VoxelGrid::index`]
+    pub voxels: [Voxel; TOTAL_VOXELS],
+    /// Voxel Grid position in world space
+    pub position: Vec3,
+    /// WGSL rounds the struct size up to a multiple of 16 so the vec3 is followed by 4 bytes of padding
+    pub _padding: u32,
+}
+
+impl Default for VoxelGrid {
+    fn default() -> Self {
+        Self::zeroed()
+    }
+}
+
+impl VoxelGrid {
+    /// Flat index of a cell. Matches `getVoxelIndex` in the voxel shader
+    pub fn index(x: usize, y: usize, z: usize) -> usize {
+        x + y * GRID_SIZE + z * GRID_SIZE * GRID_SIZE
+    }
+
+    /// Whether the cell lies inside the grid
+    pub fn in_bounds(x: i32, y: i32, z: i32) -> bool {
+        let range = 0..GRID_SIZE as i32;
+        range.contains(&x) && range.contains(&y) && range.contains(&z)
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Voxel {
+        self.voxels[Self::index(x, y, z)]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, voxel: Voxel) {
+        self.voxels[Self::index(x, y, z)] = voxel;
+    }
+
+    /// Grid space (one unit per voxel, origin at a corner) to world space. The grid is centered on its position, scaled to [`GRID_WORLD_SIZE`] and turned by the gizmo rotation
+    pub fn model_matrix(&self, rotation: DQuat) -> DMat4 {
+        DMat4::from_scale_rotation_translation(
+            DVec3::splat(GRID_WORLD_SIZE / GRID_SIZE as f64),
+            rotation,
+            self.position.as_dvec3(),
+        ) * DMat4::from_translation(DVec3::splat(-(GRID_SIZE as f64) / 2.0))
+    }
+
+    /// Number of solid voxels in the grid
+    pub fn solid_count(&self) -> usize {
+        self.voxels.iter().filter(|voxel| voxel.is_solid()).count()
+    }
+}
@@ -1,13 +1,253 @@
 use egui_wgpu::ScreenDescriptor;
 use futures::executor::block_on;
-use std::sync::Arc;
+use glam::DVec2;
+use log::{info, warn};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use wgpu::{
-    Adapter, CommandEncoder, Device, DeviceDescriptor, Instance, Queue, RenderPipeline, Surface,
-    TextureView,
+    Adapter, AdapterInfo, Backends, BindGroupLayout, CommandEncoder, Device, DeviceDescriptor,
+    Instance, PowerPreference, PresentMode, Queue, RenderPipeline, RequestDeviceError, Surface,
+    SurfaceConfiguration, TextureFormat, TextureView,
+};
+use winit::{dpi::PhysicalSize, window::Window};
+
+use crate::{
+    capture::{write_gif, write_png, Capture, CaptureKind, CaptureView},
+    cli::Args,
+    egui::gui,
+    egui_render::{AppState, EguiRenderer},
+    error::{self, Error},
+    preview::TargetPreview,
+    uniforms::{CameraUniform, CursorUniform, PaletteUniform, ScreenUniform, UniformBindings},
 };
-use winit::window::Window;
 
-use crate::{egui::gui, egui_render::EguiRenderer};
+/// Reasons no usable adapter could be picked
+#[derive(Debug)]
+pub enum AdapterError {
+    /// No adapter matched the requested backends and options
+    NotFound {
+        backends: Backends,
+        power_preference: PowerPreference,
+        force_fallback_adapter: bool,
+        /// Every adapter that was enumerated, whether or not it could present to the window
+        available: Vec<AdapterInfo>,
+    },
+    /// An adapter name was requested but none of the compatible adapters matched it
+    NameNotFound {
+        name: String,
+        available: Vec<AdapterInfo>,
+    },
+}
+
+impl fmt::Display for AdapterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let available = match self {
+            AdapterError::NotFound {
+                backends,
+                power_preference,
+                force_fallback_adapter,
+                available,
+            } => {
+                write!(
+                    f,
+                    "No graphics adapter found for backends {backends:?} with power preference {power_preference:?}"
+                )?;
+                if *force_fallback_adapter {
+                    write!(f, " using a fallback adapter")?;
+                }
+                available
+            }
+            AdapterError::NameNotFound { name, available } => {
+                write!(f, "No compatible graphics adapter is named like \"{name}\"")?;
+                available
+            }
+        };
+        if available.is_empty() {
+            write!(f, ". No adapters are available on this system")
+        } else {
+            write!(f, ". Available adapters:")?;
+            for info in available {
+                write!(
+                    f,
+                    "\n\t{} ({:?}, {:?})",
+                    info.name, info.backend, info.device_type
+                )?;
+            }
+            Ok(())
+        }
+    }
+}
+
+impl std::error::Error for AdapterError {}
+
+/// Instance of WGPU for the configured backends
+pub fn create_instance(args: &Args) -> Instance {
+    Instance::new(wgpu::InstanceDescriptor {
+        backends: args.backends(),
+        dx12_shader_compiler: wgpu::Dx12Compiler::Fxc,
+        flags: wgpu::InstanceFlags::empty(),
+        gles_minor_version: wgpu::Gles3MinorVersion::Automatic,
+    })
+}
+
+/// Enumerates and logs every adapter for the configured backends, then picks one that can present to the surface. Without a surface, as when capturing headless, any adapter will do
+pub fn select_adapter(
+    instance: &Instance,
+    surface: Option<&Surface>,
+    args: &Args,
+) -> Result<Adapter, AdapterError> {
+    let supports_surface =
+        |adapter: &Adapter| surface.is_none_or(|surface| adapter.is_surface_supported(surface));
+    let backends = args.backends();
+    let adapters = instance.enumerate_adapters(backends);
+    for adapter in &adapters {
+        let info = adapter.get_info();
+        info!(
+            "Found adapter {} ({:?}, {:?}), driver {} {}, surface supported: {}",
+            info.name,
+            info.backend,
+            info.device_type,
+            info.driver,
+            info.driver_info,
+            supports_surface(adapter)
+        );
+    }
+    let available = adapters.iter().map(Adapter::get_info).collect::<Vec<_>>();
+
+    let adapter = match args.adapter_name() {
+        Some(name) => adapters
+            .into_iter()
+            .find(|adapter| {
+                supports_surface(adapter) && adapter.get_info().name.to_lowercase().contains(&name)
+            })
+            .ok_or(AdapterError::NameNotFound { name, available })?,
+        // Block on is me just handling a future lazily, I can likely do something better
+        None => block_on(instance.request_adapter(&args.adapter_options(surface))).ok_or(
+            AdapterError::NotFound {
+                backends,
+                power_preference: args.power_preference(),
+                force_fallback_adapter: args.fallback_adapter,
+                available,
+            },
+        )?,
+    };
+
+    let info = adapter.get_info();
+    info!("Using adapter {} ({:?})", info.name, info.backend);
+    if info.device_type == wgpu::DeviceType::Cpu && !args.fallback_adapter {
+        warn!("Selected adapter is a software renderer, expect poor performance");
+    }
+    Ok(adapter)
+}
+
+/// Picks the requested present mode if the surface supports it. The auto modes are resolved by WGPU itself, anything else falls back to fifo which is always supported
+fn select_present_mode(requested: PresentMode, supported: &[PresentMode]) -> PresentMode {
+    match requested {
+        PresentMode::AutoVsync | PresentMode::AutoNoVsync => requested,
+        mode if supported.contains(&mode) => mode,
+        mode => {
+            warn!(
+                "Present mode {mode:?} isn't supported by this surface ({supported:?}), using Fifo"
+            );
+            PresentMode::Fifo
+        }
+    }
+}
+
+/// Requests a connection to a physical device, creating a logical device. Returns the Device together with a Queue that executes command buffers
+pub fn request_device(adapter: &Adapter) -> Result<(Device, Queue), RequestDeviceError> {
+    block_on(adapter.request_device(
+        &DeviceDescriptor {
+            label: None,
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits::default(),
+            memory_hints: Default::default(),
+        },
+        None,
+    ))
+}
+
+/// The voxel raymarching pipeline, drawing into targets of `format`. The window, the target preview and captures each use one
+pub fn create_render_pipeline(
+    device: &Device,
+    layout: &BindGroupLayout,
+    format: TextureFormat,
+) -> RenderPipeline {
+    // WGSL Shader initialization
+    // Alternatively let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Shader"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("shaders/voxel_shader.wgsl").into()),
+    });
+
+    // This is the render pipeline layout
+    // Vertex shaders are necessary while fragment shaders are not because the rasterization pipeline still expects something to define where the fragment shader runs. In essence vertex shaders at a minimum describe the screen where fragment shaders are run in graphics pipelines
+    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Render Pipeline Layout"),
+        bind_group_layouts: &[layout],
+        push_constant_ranges: &[],
+    });
+
+    //
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Render Pipeline"),
+        layout: Some(&render_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            // Entry point is the function we want to point to in the shader
+            entry_point: "vs_main",
+            // buffers field tells wgpu what type of vertices we want to pass to the vertex shader. We're specifying the vertices in the vertex shader itself, so we'll leave this empty.
+            // ! Connects to @location function input for vertex shader
+            buffers: &[],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        // fragment is optional, so you have to wrap it in Some(). We need it if we want to store color data to the surface
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                // The targets field tells wgpu what color outputs it should set up. Currently, we only need one for the surface. We use the surface's format so that copying to it is easy, and we specify that the blending should just replace old pixel data with new data. We also tell wgpu to write to all colors: red, blue, green, and alpha
+                // ! Connects to @location function output for fragment shader
+                format,
+                // blend specifies how the colors will interact with the background
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        //
+        primitive: wgpu::PrimitiveState {
+            // Using PrimitiveTopology::TriangleList means that every three vertices will correspond to one triangle
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            // The front_face and cull_mode fields tell wgpu how to determine whether a given triangle is facing forward or not. FrontFace::Ccw means that a triangle is facing forward if the vertices are arranged in a counter-clockwise direction.
+            front_face: wgpu::FrontFace::Ccw,
+            // Triangles that are not considered facing forward are culled (not included in the render) as specified by CullMode::Back
+            cull_mode: Some(wgpu::Face::Back),
+            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+            polygon_mode: wgpu::PolygonMode::Fill,
+            // Requires Features::DEPTH_CLIP_CONTROL
+            unclipped_depth: false,
+            // Requires Features::CONSERVATIVE_RASTERIZATION
+            conservative: false,
+        },
+        // We're not using a depth/stencil buffer currently, so we leave depth_stencil as None. This will change later.
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1, // count determines how many samples the pipeline will use. Multisampling is a complex topic, so we won't get into it here.
+            mask: !0, // mask specifies which samples should be active. In this case, we are using all of them
+            alpha_to_coverage_enabled: false, // alpha_to_coverage_enabled has to do with anti-aliasing. We're not covering anti-aliasing here
+        },
+        multiview: None, // multiview indicates how many array layers the render attachments can have. We won't be rendering to array textures, so we can set this to None
+        cache: None, // cache allows wgpu to cache shader compilation data. Only really useful for Android build targets
+    })
+}
 
 /// This stores the WGPU state for the window
 pub struct WgpuState {
@@ -18,126 +258,102 @@ pub struct WgpuState {
     pub queue: Queue,
     pub window: Arc<Window>,
     pub render_pipeline: RenderPipeline,
+    /// Layout of the voxel shader's uniforms, kept for views created after startup like captures
+    pub bind_group_layout: BindGroupLayout,
     pub egui: EguiRenderer,
+    /// Current surface configuration. Only changes when the window is resized, so it isn't rebuilt every frame
+    pub config: SurfaceConfiguration,
+    /// Uniforms for the full window scene showing the user's build
+    pub scene: UniformBindings,
+    /// Offscreen view of the target structure, shown in the controls next to the scene
+    pub preview: TargetPreview,
+    /// Set from the device lost callback once the driver has reset or removed the device
+    device_lost: Arc<AtomicBool>,
 }
 
 impl WgpuState {
-    pub fn new(window: Arc<Window>) -> WgpuState {
+    pub fn new(
+        window: Arc<Window>,
+        args: &Args,
+        app_state: AppState,
+        egui_state: egui_winit::State,
+    ) -> error::Result<WgpuState> {
         // Instance of WGPU
-        let instance = Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            dx12_shader_compiler: wgpu::Dx12Compiler::Fxc,
-            flags: wgpu::InstanceFlags::empty(),
-            gles_minor_version: wgpu::Gles3MinorVersion::Automatic,
-        });
+        let instance = create_instance(args);
 
         // Surface upon which WGPU acts
-        let surface: Surface = instance
-            .create_surface(window.clone())
-            .expect("Unable to get surface from window handle");
+        let surface: Surface = instance.create_surface(window.clone())?;
 
         // Handle to physical graphics and/or compute device
-        // Block on is me just handling a future lazily, I can likely do something better
-        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::default(),
-            compatible_surface: Some(&surface),
-            force_fallback_adapter: false,
-        }))
-        .expect("Unable to get adapter");
-
-        // Requests a connection to a physical device, creating a logical device. Returns the Device together with a Queue that executes command buffers.
-        let (device, queue) = block_on(adapter.request_device(
-            &DeviceDescriptor {
-                label: None,
-                required_features: wgpu::Features::empty(),
-                required_limits: wgpu::Limits::default(),
-                memory_hints: Default::default(),
-            },
-            None,
-        ))
-        .expect("Unable to get device and queue");
-
-        // WGSL Shader initialization
-        // Alternatively let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/voxel_shader.wgsl").into()),
-        });
+        let adapter = select_adapter(&instance, Some(&surface), args)?;
 
-        // This is the render pipeline layout
-        // Vertex shaders are necessary while fragment shaders are not because the rasterization pipeline still expects something to define where the fragment shader runs. In essence vertex shaders at a minimum describe the screen where fragment shaders are run in graphics pipelines
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[],
-                push_constant_ranges: &[],
-            });
+        let (device, queue) = request_device(&adapter)?;
 
-        //
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                // Entry point is the function we want to point to in the shader
-                entry_point: "vs_main",
-                // buffers field tells wgpu what type of vertices we want to pass to the vertex shader. We're specifying the vertices in the vertex shader itself, so we'll leave this empty.
-                // ! Connects to @location function input for vertex shader
-                buffers: &[],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            // fragment is optional, so you have to wrap it in Some(). We need it if we want to store color data to the surface
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    // The targets field tells wgpu what color outputs it should set up. Currently, we only need one for the surface. We use the surface's format so that copying to it is easy, and we specify that the blending should just replace old pixel data with new data. We also tell wgpu to write to all colors: red, blue, green, and alpha
-                    // ! Connects to @location function output for fragment shader
-                    format: surface
-                        .get_capabilities(&adapter)
-                        .formats
-                        .get(0)
-                        .expect("No Format Present")
-                        .clone(),
-                    // blend specifies how the colors will interact with the background
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            //
-            primitive: wgpu::PrimitiveState {
-                // Using PrimitiveTopology::TriangleList means that every three vertices will correspond to one triangle
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                // The front_face and cull_mode fields tell wgpu how to determine whether a given triangle is facing forward or not. FrontFace::Ccw means that a triangle is facing forward if the vertices are arranged in a counter-clockwise direction.
-                front_face: wgpu::FrontFace::Ccw,
-                // Triangles that are not considered facing forward are culled (not included in the render) as specified by CullMode::Back
-                cull_mode: Some(wgpu::Face::Back),
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            // We're not using a depth/stencil buffer currently, so we leave depth_stencil as None. This will change later.
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1, // count determines how many samples the pipeline will use. Multisampling is a complex topic, so we won't get into it here.
-                mask: !0, // mask specifies which samples should be active. In this case, we are using all of them
-                alpha_to_coverage_enabled: false, // alpha_to_coverage_enabled has to do with anti-aliasing. We're not covering anti-aliasing here
-            },
-            multiview: None, // multiview indicates how many array layers the render attachments can have. We won't be rendering to array textures, so we can set this to None
-            cache: None, // cache allows wgpu to cache shader compilation data. Only really useful for Android build targets
+        // Driver resets leave the device unusable. Flag it so the window can rebuild everything on the next frame instead of panicking
+        let device_lost = Arc::new(AtomicBool::new(false));
+        let lost_flag = device_lost.clone();
+        device.set_device_lost_callback(move |reason, message| match reason {
+            // Both of these fire during normal teardown or when we replace the callback
+            wgpu::DeviceLostReason::Dropped | wgpu::DeviceLostReason::ReplacedCallback => {}
+            _ => {
+                warn!("GPU device lost ({reason:?}): {message}");
+                lost_flag.store(true, Ordering::SeqCst);
+            }
         });
 
-        let egui = EguiRenderer::new(
+        // The first format is the surface's preferred one. Both the scene pipeline and egui render into it
+        let capabilities = surface.get_capabilities(&adapter);
+        let surface_format = *capabilities.formats.first().ok_or(Error::NoSurfaceFormat)?;
+        let present_mode = select_present_mode(args.vsync.into(), &capabilities.present_modes);
+
+        // The surface has to be configured before any frame can be taken from it. Zero sized windows are clamped since WGPU rejects them
+        let size = window.inner_size();
+        let config = SurfaceConfiguration {
+            present_mode,
+            ..surface
+                .get_default_config(&adapter, size.width.max(1), size.height.max(1))
+                .ok_or(Error::SurfaceConfig {
+                    width: size.width,
+                    height: size.height,
+                })?
+        };
+        surface.configure(&device, &config);
+
+        // Uniforms read by the voxel shader. The screen holds the real resolution, the camera and grid are refreshed before every frame from the app state
+        let bind_group_layout = UniformBindings::layout(&device);
+        let scene = UniformBindings::new(
+            &device,
+            &bind_group_layout,
+            "Scene",
+            ScreenUniform::new(config.width, config.height),
+            &app_state.grid,
+        );
+
+        let render_pipeline = create_render_pipeline(&device, &bind_group_layout, surface_format);
+
+        let mut app_state = app_state;
+        app_state
+            .camera
+            .projection
+            .set_aspect_ratio(config.width as f64 / config.height as f64);
+        let mut egui = EguiRenderer::new(
             &device,
             window.clone(), // winit Window
+            surface_format,
+            app_state,
+            egui_state,
         );
+        // The preview renders with the same pipeline, so its texture has to use the surface format too. Egui gets a fresh id for it on every rebuild
+        let preview = TargetPreview::new(
+            &device,
+            &bind_group_layout,
+            surface_format,
+            &egui.app_state_mut().challenge.target,
+        );
+        egui.app_state_mut().preview.texture =
+            Some(egui.register_native_texture(&device, &preview.view));
 
-        WgpuState {
+        Ok(WgpuState {
             instance,
             surface,
             adapter,
@@ -145,25 +361,154 @@ impl WgpuState {
             queue,
             window,
             render_pipeline,
+            bind_group_layout,
             egui,
+            config,
+            scene,
+            preview,
+            device_lost,
+        })
+    }
+
+    /// Reconfigures the surface for a new window size and updates the screen uniform. Zero sized windows (minimized) are ignored
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        if size.width == 0 || size.height == 0 {
+            return;
         }
+        self.config.width = size.width;
+        self.config.height = size.height;
+        self.surface.configure(&self.device, &self.config);
+        self.scene
+            .write_screen(&self.queue, ScreenUniform::new(size.width, size.height));
+        self.egui
+            .app_state_mut()
+            .camera
+            .projection
+            .set_aspect_ratio(size.width as f64 / size.height as f64);
     }
 
-    // This draws egui upon the screen
+    /// Uploads the cameras and grids from the app state. Called before the scene and preview are drawn. Returns true while the view is animating and more frames are needed
+    pub fn update_uniforms(&mut self) -> bool {
+        let app_state = self.egui.app_state_mut();
+        let animating = app_state.step_animation();
+        let camera = CameraUniform::new(&mut app_state.camera, &app_state.grid, app_state.rotation);
+        self.scene.write_camera(&self.queue, camera);
+        self.scene.write_grid(&self.queue, &app_state.grid);
+        // Picked again every frame since the camera or rotation may have moved under a still pointer
+        let viewport = DVec2::new(self.config.width as f64, self.config.height as f64);
+        let cursor = match app_state.keyboard_cursor {
+            // The keyboard cursor selects a cell directly, so it is either filled or emptied in place
+            Some(cell) => {
+                let allowed = app_state.can_place(cell) || app_state.can_remove(cell);
+                CursorUniform::cell(cell, allowed, app_state.selected_color)
+            }
+            None => {
+                let hover = app_state.hover(viewport);
+                let allowed = hover.is_some_and(|pick| app_state.can_place(pick.placement()));
+                CursorUniform::new(hover, allowed, app_state.selected_color)
+            }
+        };
+        self.scene.write_cursor(&self.queue, cursor);
+        let palette = PaletteUniform::new(app_state.palette, app_state.patterns);
+        self.scene.write_palette(&self.queue, palette);
+        self.preview.bindings.write_palette(&self.queue, palette);
+        let spinning = app_state.preview.step();
+        let rotation =
+            app_state.preview.rotation(app_state.rotation) * app_state.challenge.preview_offset();
+        let target = &app_state.challenge.target;
+        self.preview.bindings.write_grid(&self.queue, target);
+        let camera = CameraUniform::new(&mut app_state.preview.camera, target, rotation);
+        self.preview.bindings.write_camera(&self.queue, camera);
+        animating || spinning
+    }
+
+    /// Renders the scene as it is on screen at the window's size and saves it to the working directory. Encoding runs on its own thread so a long turntable doesn't freeze the window
+    pub fn capture(&mut self, kind: CaptureKind, turntable_frames: u32) {
+        let capture = Capture::new(
+            &self.device,
+            &self.bind_group_layout,
+            self.config.width,
+            self.config.height,
+        );
+        let view = CaptureView::from_app_state(self.egui.app_state_mut());
+        let frames = match kind {
+            CaptureKind::Screenshot => capture
+                .frame(&self.device, &self.queue, &view)
+                .map(|image| vec![image]),
+            CaptureKind::Turntable => {
+                capture.turntable(&self.device, &self.queue, &view, turntable_frames)
+            }
+        };
+        let mut frames = match frames {
+            Ok(frames) => frames,
+            Err(e) => {
+                log::error!("{e}");
+                return;
+            }
+        };
+        let path = kind.default_path();
+        std::thread::spawn(move || {
+            let saved = match kind {
+                CaptureKind::Screenshot => write_png(&path, &frames.remove(0)),
+                CaptureKind::Turntable => write_gif(&path, frames),
+            };
+            match saved {
+                Ok(()) => info!("Saved capture to {}", path.display()),
+                Err(e) => log::error!("{e}"),
+            }
+        });
+    }
+
+    /// Whether the device was lost and the state has to be rebuilt with [`WgpuState::rebuild`]
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::SeqCst)
+    }
+
+    /// Flags the state for a rebuild on the next frame, for losses noticed outside the device lost callback such as a lost surface
+    pub fn mark_lost(&self) {
+        self.device_lost.store(true, Ordering::SeqCst);
+    }
+
+    /// Tears down every GPU resource and creates them again for the same window. The app state, including the in-progress grid, is carried over
+    pub fn rebuild(self, args: &Args) -> error::Result<WgpuState> {
+        let WgpuState {
+            instance,
+            surface,
+            adapter,
+            device,
+            queue,
+            window,
+            render_pipeline,
+            bind_group_layout,
+            egui,
+            config: _,
+            scene,
+            preview,
+            device_lost: _,
+        } = self;
+        let (egui_state, app_state) = egui.into_parts();
+        // Some platforms refuse a second surface on a window that still has one, so the old resources have to be gone before WgpuState::new runs. Everything built on the device goes first, the instance last
+        drop((scene, preview, render_pipeline, bind_group_layout));
+        drop(surface);
+        drop((queue, device, adapter));
+        drop(instance);
+        WgpuState::new(window, args, app_state, egui_state)
+    }
+
+    // This draws egui upon the screen. Returns how long egui is happy to wait before the next repaint
     pub fn draw(
         &mut self,
         encoder: &mut CommandEncoder,
         window_surface_view: &TextureView,
         screen_descriptor: ScreenDescriptor,
-    ) {
+    ) -> Duration {
         self.egui.draw(
             &self.device,
             &self.queue,
             encoder,
-            &self.window,
             window_surface_view,
             screen_descriptor,
             gui,
-        );
+        )
     }
 }
@@ -1,46 +1,148 @@
-use crate::wgpu::WgpuState;
+use crate::{
+    challenge::Challenge,
+    cli::Args,
+    egui::UI_SCALE,
+    egui_render::{AppState, EguiRenderer},
+    error::{Error, Result},
+    keyboard,
+    locale::Catalog,
+    orbit::OrbitController,
+    replay::{Input, Recorder, Replay},
+    session::{ChallengeSession, TransitionHook},
+    theme::Theme,
+    wgpu::WgpuState,
+};
 use egui_wgpu::ScreenDescriptor;
-use std::sync::Arc;
-use wgpu::{
-    util::{BufferInitDescriptor, DeviceExt},
-    Surface,
+use egui_winit::accesskit_winit;
+use glam::DVec2;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
 };
 use winit::{
-    application::ApplicationHandler, event::WindowEvent, event_loop::ActiveEventLoop,
+    application::ApplicationHandler,
+    event::{ElementState, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoopProxy},
+    keyboard::{Key, ModifiersState},
     window::Window,
 };
 
+/// Events sent to the event loop from outside winit
+#[derive(Debug)]
+pub enum UserEvent {
+    /// A screen reader asked for the accessibility tree or performed an action
+    AccessKit(accesskit_winit::Event),
+}
+
+impl From<accesskit_winit::Event> for UserEvent {
+    fn from(event: accesskit_winit::Event) -> Self {
+        UserEvent::AccessKit(event)
+    }
+}
+
 /// This stores the main window and associated WGPU state
-#[derive(Default)]
 pub struct Win {
     window: Option<Arc<Window>>,
     wgpu_state: Option<WgpuState>,
+    args: Args,
+    /// The error that stopped the event loop, if any
+    error: Option<Error>,
+    /// When the next frame should be drawn. None means nothing on screen is changing so the loop can sleep until the next event
+    redraw_at: Option<Instant>,
+    /// When the last frame started, used to enforce the FPS cap
+    last_frame: Option<Instant>,
+    /// Mouse driven camera controls for the 3D viewport
+    orbit: OrbitController,
+    /// Lets AccessKit wake the event loop when a screen reader needs something
+    proxy: EventLoopProxy<UserEvent>,
+    /// Handed to the app state once the window exists
+    theme: Theme,
+    /// Modifier keys held right now. Key events don't carry them
+    modifiers: ModifiersState,
+    /// Where input is written with --record
+    recorder: Option<Recorder>,
+    /// Input still to be played with --replay. Live input is ignored until it runs out
+    replay: Option<Replay>,
 }
 
 impl Win {
-    pub fn init(&mut self, event_loop: &ActiveEventLoop) {
-        self.window = Some(Arc::new(
-            event_loop
-                .create_window(Window::default_attributes().with_title("MineCaptcha"))
-                .expect("Couldn't create window"),
-        ));
-        self.wgpu_state = Some(WgpuState::new(self.window.as_ref().unwrap().clone()));
+    pub fn new(args: Args, theme: Theme, proxy: EventLoopProxy<UserEvent>) -> Self {
+        Self {
+            window: None,
+            wgpu_state: None,
+            args,
+            error: None,
+            redraw_at: None,
+            last_frame: None,
+            orbit: OrbitController::default(),
+            proxy,
+            theme,
+            modifiers: ModifiersState::empty(),
+            recorder: None,
+            replay: None,
+        }
     }
-}
 
-impl ApplicationHandler for Win {
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        if self.window.is_none() {
-            self.init(event_loop);
+    pub fn init(&mut self, event_loop: &ActiveEventLoop) -> Result<()> {
+        // The window stays hidden until AccessKit is attached, which has to happen before it is first shown
+        let catalog = Catalog::new(self.args.locale().as_deref());
+        log::info!("Showing text in {}", catalog.language());
+        let attributes = self
+            .args
+            .window_attributes(catalog.get("window-title"))
+            .with_visible(false);
+        let window = Arc::new(event_loop.create_window(attributes)?);
+        self.window = Some(window.clone());
+        let egui_state = EguiRenderer::winit_state(&window, self.proxy.clone());
+        let on_transition: Option<TransitionHook> = if self.args.report_state {
+            Some(Box::new(|transition| println!("{transition}")))
+        } else {
+            None
+        };
+        let session = ChallengeSession::new(self.args.max_attempts, on_transition);
+        let challenge = Challenge::new(self.args.challenge_spec())?;
+        let mut app_state = AppState::new(challenge, session, catalog);
+        app_state.text_mode = self.args.text_mode;
+        app_state.palette = self.args.palette;
+        app_state.patterns = self.args.patterns;
+        app_state.theme = self.theme.clone();
+        let wgpu_state = self.wgpu_state.insert(WgpuState::new(
+            window.clone(),
+            &self.args,
+            app_state,
+            egui_state,
+        )?);
+        let zoom = self.args.ui_scale.clamp(*UI_SCALE.start(), *UI_SCALE.end());
+        wgpu_state.egui.context.set_zoom_factor(zoom);
+        window.set_visible(true);
+        // The timer only starts once everything is ready to draw the challenge, and recordings count from the same moment so replays line up with it
+        let now = Instant::now();
+        let app_state = wgpu_state.egui.app_state_mut();
+        app_state.session.start(&app_state.challenge, now);
+        if let Some(path) = &self.args.record {
+            self.recorder = Some(Recorder::create(path, now)?);
+        }
+        if let Some(path) = &self.args.replay {
+            self.replay = Some(Replay::load(path, now)?);
         }
+        Ok(())
     }
 
-    fn window_event(
-        &mut self,
-        event_loop: &winit::event_loop::ActiveEventLoop,
-        _window_id: winit::window::WindowId,
-        event: winit::event::WindowEvent,
-    ) {
+    /// Rebuilds the GPU state if the device was lost since the last frame
+    fn recover_lost_device(&mut self) -> Result<()> {
+        if let Some(wgpu_state) = self.wgpu_state.take() {
+            if wgpu_state.is_device_lost() {
+                log::warn!("Rebuilding GPU state after device loss");
+                self.wgpu_state = Some(wgpu_state.rebuild(&self.args)?);
+            } else {
+                self.wgpu_state = Some(wgpu_state);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reacts to a window event, whether it came from winit or from a replay
+    fn handle_window_event(&mut self, event_loop: &ActiveEventLoop, event: WindowEvent) {
         match event {
             // This is the event which closes our window
             WindowEvent::CloseRequested => {
@@ -48,133 +150,332 @@ impl ApplicationHandler for Win {
                 event_loop.exit();
             }
             // This is the primary way to animate and redraw the image on the screen
-            WindowEvent::RedrawRequested => {
-                if let Some(window) = self.window.as_mut() {
-                    if let Some(wgpu_state) = self.wgpu_state.as_mut() {
-                        // If you alter the screen continuously it will likely cause an Outdated error as the screen itself is different from when you requested it. This is why we add a loop to continuously request until the user stops resizing the screen
-                        let (output_texture, size) = loop {
-                            // Gets screen size and checks if either width or height is 0. The code will panic if either is true so don't
-                            let size = window.inner_size();
-                            if size.width > 0 && size.height > 0 {
-                                // Configure screen surface size based on the current surface and adapter
-                                let surf_conf = Surface::get_default_config(
-                                    &wgpu_state.surface,
-                                    &wgpu_state.adapter,
-                                    size.width,
-                                    size.height,
-                                )
-                                .expect("Unable to create resized configuration");
-                                wgpu_state.surface.configure(&wgpu_state.device, &surf_conf);
-
-                                // Configure what the screen renders
-                                // This grabs a frame from the surface to render to
-                                match wgpu_state.surface.get_current_texture() {
-                                    Ok(surf_text) => break (surf_text, size),
-                                    Err(e) => match e {
-                                        wgpu::SurfaceError::Outdated => {
-                                            println!("Outdated Surface");
-                                            // Optionally, you can add a small delay here to avoid tight loops
-                                            // std::thread::sleep(std::time::Duration::from_millis(10));
-                                            continue;
-                                        }
-                                        _ => panic!("Failed to get frame"),
-                                    },
-                                }
-                            }
-                        };
-
-                        // This line creates a TextureView with default settings. We need to do this because we want to control how the render code interacts with the texture. This TextureView describes a texture and associated metadata
-                        let view = output_texture
-                            .texture
-                            .create_view(&wgpu::TextureViewDescriptor::default());
-                        // We also need to create a CommandEncoder to create the actual commands to send to the GPU. Most modern graphics frameworks expect commands to be stored in a command buffer before being sent to the GPU. The encoder builds a command buffer that we can then send to the GPU.
-                        let mut encoder = wgpu_state.device.create_command_encoder(
-                            &wgpu::CommandEncoderDescriptor {
-                                label: Some("Render Encoder"),
-                            },
-                        );
-                        // The reason this is in a seperate block is that begin_render_pass() borrows encoder mutably (aka &mut self). We can't call encoder.finish() until we release that mutable borrow. If we don't do this then we get error `Command encoder is locked by a previously created render/compute pass. Before recording any new commands, the pass must be ended`. you can also use drop(render_pass) to achieve the same effect
-                        {
-                            // Encodes a single rendered pass of a screen
-                            // RenderPassDescriptor describes the attachments of a render pass
-                            let mut render_pass =
-                                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                                    // Debug Label
-                                    label: Some("Render Pass"),
-                                    // color_attachments describe where we are going to draw our color to
-                                    // RenderPassColorAttachment has view field, which informs wgpu what texture to save the colors to, a resolve_target is the texture that will receive the resolved output. This will be the same as view unless multisampling is enabled, the ops field takes a wgpu::Operations object. This tells wgpu what to do with the colors on the screen (specified by view).
-                                    // ! Color is the background color
-                                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                                        view: &view,
-                                        resolve_target: None,
-                                        ops: wgpu::Operations {
-                                            load: wgpu::LoadOp::Clear(wgpu::Color {
-                                                r: 0.17,
-                                                g: 0.60,
-                                                b: 0.88,
-                                                a: 1.0,
-                                            }),
-                                            store: wgpu::StoreOp::Store,
-                                        },
-                                    })],
-                                    depth_stencil_attachment: None,
-                                    occlusion_query_set: None,
-                                    timestamp_writes: None,
-                                });
-
-                            /*
-                            Indeces to reduce vertex count aren't working so something is likely misconfigured. Resolve later
-
-                            let indices: [u8; 6] = [0, 1, 2, 1, 3, 2]; // Define the indices for two triangles. Indices are how the triangle orients vertex placement and overlap
-
-                            // Create the index buffer
-                            let index_buffer = wgpu_state.device.create_buffer_init(
-                                &wgpu::util::BufferInitDescriptor {
-                                    label: Some("Index Buffer"),
-                                    contents: &indices,
-                                    usage: wgpu::BufferUsages::INDEX,
-                                },
-                            );
-
-                            // Set the index buffer to allow drawing a square with fewer or overlapping verticies
-                            render_pass.set_index_buffer(
-                                index_buffer.slice(..),
-                                wgpu::IndexFormat::Uint32,
-                            ); // Set the index buffer
-                            */
-
-                            // Set the render pipeline to integrate the shader
-                            render_pass.set_pipeline(&wgpu_state.render_pipeline);
-
-                            // ! We tell wgpu to draw something with the given range of vertices and one instance. This is where @builtin(vertex_index) comes from.
-                            render_pass.draw(0..6, 0..1);
-                        }
-
-                        // ! Can only use size one size otherwise it crashes
-                        let screen_descriptor = ScreenDescriptor {
-                            size_in_pixels: [size.width, size.height],
-                            pixels_per_point: wgpu_state.window.scale_factor() as f32,
-                        };
-
-                        // Draws egui
-                        wgpu_state.draw(&mut encoder, &view, screen_descriptor);
-
-                        // Submits an iterator of the render command buffer to the queue
-                        wgpu_state.queue.submit(std::iter::once(encoder.finish()));
-                        // Schedule texture to be presented on the owned surface
-                        output_texture.present();
-                    }
+            // Frames are only drawn when something asked for one, so a static puzzle doesn't keep a core busy
+            WindowEvent::RedrawRequested => match self.redraw() {
+                Ok(repaint_delay) => self.schedule_redraw(repaint_delay),
+                Err(e) => {
+                    self.fail(event_loop, e);
+                    return;
+                }
+            },
+            // The surface configuration is cached, so it only changes here instead of every frame
+            WindowEvent::Resized(size) => {
+                if let Some(wgpu_state) = self.wgpu_state.as_mut() {
+                    wgpu_state.resize(size);
+                }
+                self.schedule_redraw(Duration::ZERO);
+            }
+            // The physical size changes with the scale factor. Winit usually follows up with Resized but not on every platform
+            WindowEvent::ScaleFactorChanged { .. } => {
+                if let Some(wgpu_state) = self.wgpu_state.as_mut()
+                    && let Some(window) = self.window.as_ref()
+                {
+                    wgpu_state.resize(window.inner_size());
                 }
-                // This is what actually causes the redraw event to be emitted
-                self.window.as_ref().unwrap().request_redraw();
+                self.schedule_redraw(Duration::ZERO);
             }
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
             _ => (),
         }
+        // Camera controls only see input egui isn't using, so dragging a window or the gizmo doesn't also orbit
+        if let Some(wgpu_state) = self.wgpu_state.as_mut() {
+            let context = &wgpu_state.egui.context;
+            let egui_claims = match &event {
+                WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    ..
+                }
+                | WindowEvent::MouseWheel { .. } => {
+                    context.is_pointer_over_area() || context.wants_pointer_input()
+                }
+                WindowEvent::KeyboardInput { .. } => context.wants_keyboard_input(),
+                _ => false,
+            };
+            if !egui_claims && let Some(kind) = keyboard::capture_request(&event, self.modifiers) {
+                wgpu_state.capture(kind, self.args.turntable_frames);
+            }
+            let viewport = DVec2::new(
+                wgpu_state.config.width as f64,
+                wgpu_state.config.height as f64,
+            );
+            let app_state = wgpu_state.egui.app_state_mut();
+            // Both see every event so the keyboard cursor works alongside the mouse controls
+            let keyboard_changed = !egui_claims && keyboard::handle_event(&event, app_state);
+            let orbit_changed =
+                !egui_claims && self.orbit.handle_event(&event, app_state, viewport);
+            if keyboard_changed || orbit_changed {
+                self.schedule_redraw(Duration::ZERO);
+            }
+        }
         // ! EGUI event handling or what allows egui state to update and function
         if let Some(wgpu_state) = self.wgpu_state.as_mut()
             && let Some(window) = self.window.as_ref()
+            && wgpu_state.egui.handle_input(window, &event)
+        {
+            self.schedule_redraw(Duration::ZERO);
+        }
+    }
+
+    /// Plays every replayed input whose time has come through the same paths as live input
+    fn play_replay(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(replay) = self.replay.as_mut() else {
+            return;
+        };
+        let inputs = replay.due(Instant::now());
+        if replay.next_at().is_none() {
+            log::info!("Replay finished, taking input from the user again");
+            self.replay = None;
+        }
+        for input in inputs {
+            match (input.to_event(), input) {
+                (Some(event), _) => self.handle_window_event(event_loop, event),
+                (None, Input::Key { key, state, repeat }) => self.replay_key(&key, state, repeat),
+                (None, _) => {}
+            }
+        }
+    }
+
+    /// Replays a key press or release. Winit doesn't allow building key events, so egui and the key handling get the key directly
+    fn replay_key(&mut self, key: &Key, state: ElementState, repeat: bool) {
+        let Some(wgpu_state) = self.wgpu_state.as_mut() else {
+            return;
+        };
+        let pressed = state == ElementState::Pressed;
+        if pressed && !wgpu_state.egui.context.wants_keyboard_input() {
+            if let Some(kind) = keyboard::capture_key(key.as_ref(), repeat, self.modifiers) {
+                wgpu_state.capture(kind, self.args.turntable_frames);
+            }
+            keyboard::handle_key(key.as_ref(), wgpu_state.egui.app_state_mut());
+        }
+        wgpu_state.egui.handle_key(key, pressed, repeat);
+        self.schedule_redraw(Duration::ZERO);
+    }
+
+    /// Takes the error that stopped the event loop so `main` can report it
+    pub fn take_error(&mut self) -> Option<Error> {
+        self.error.take()
+    }
+
+    /// Stores the error and stops the event loop. Winit's callbacks can't return errors so this is how they leave the loop
+    fn fail(&mut self, event_loop: &ActiveEventLoop, error: Error) {
+        log::error!("{error}");
+        self.error = Some(error);
+        event_loop.exit();
+    }
+
+    /// Asks for a frame after `delay`, respecting the FPS cap. A delay too large to represent (egui uses Duration::MAX when idle) schedules nothing
+    fn schedule_redraw(&mut self, delay: Duration) {
+        let Some(mut at) = Instant::now().checked_add(delay) else {
+            return;
+        };
+        if let (Some(min_frame_time), Some(last_frame)) =
+            (self.args.min_frame_time(), self.last_frame)
+        {
+            at = at.max(last_frame + min_frame_time);
+        }
+        // Keep whichever request is due first
+        self.redraw_at = Some(self.redraw_at.map_or(at, |current| current.min(at)));
+    }
+
+    /// Renders a single frame of the scene and egui to the window surface. Returns the delay before the next frame is needed
+    fn redraw(&mut self) -> Result<Duration> {
+        self.last_frame = Some(Instant::now());
+        self.recover_lost_device()?;
+        let (Some(window), Some(wgpu_state)) = (self.window.as_ref(), self.wgpu_state.as_mut())
+        else {
+            return Ok(Duration::MAX);
+        };
+        // Gets screen size and checks if either width or height is 0. The code will panic if either is true so skip the frame. A Resized event will ask for a new one
+        if window.inner_size().width == 0 || window.inner_size().height == 0 {
+            return Ok(Duration::MAX);
+        }
+        // If you alter the screen continuously it will likely cause an Outdated error as the screen itself is different from when you requested it. This is why we add a loop to continuously request until the user stops resizing the screen
+        let output_texture = loop {
+            // Configure what the screen renders
+            // This grabs a frame from the surface to render to
+            match wgpu_state.surface.get_current_texture() {
+                Ok(surf_text) => break surf_text,
+                Err(e) => match e {
+                    // The surface no longer matches the window, so configure it again for the current size
+                    wgpu::SurfaceError::Outdated => {
+                        log::warn!("Surface is outdated, configuring it again");
+                        wgpu_state.resize(window.inner_size());
+                        continue;
+                    }
+                    // Configuring a lost surface again doesn't bring it back, so the next frame rebuilds the whole GPU state
+                    wgpu::SurfaceError::Lost => {
+                        log::warn!("Surface lost");
+                        wgpu_state.mark_lost();
+                        return Ok(Duration::ZERO);
+                    }
+                    // The GPU took too long to hand out a frame. Drop this one and try again on the next redraw
+                    wgpu::SurfaceError::Timeout => return Ok(Duration::ZERO),
+                    wgpu::SurfaceError::OutOfMemory => return Err(Error::Frame(e)),
+                },
+            }
+        };
+
+        let app_state = wgpu_state.egui.app_state_mut();
+        app_state.session.tick(&app_state.challenge, Instant::now());
+        let animating = wgpu_state.update_uniforms();
+        let background = wgpu_state.egui.app_state_mut().theme.clear_color();
+
+        // This line creates a TextureView with default settings. We need to do this because we want to control how the render code interacts with the texture. This TextureView describes a texture and associated metadata
+        let view = output_texture
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        // We also need to create a CommandEncoder to create the actual commands to send to the GPU. Most modern graphics frameworks expect commands to be stored in a command buffer before being sent to the GPU. The encoder builds a command buffer that we can then send to the GPU.
+        let mut encoder =
+            wgpu_state
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Render Encoder"),
+                });
+        // The preview texture has to be up to date before egui samples it further down this encoder
+        wgpu_state
+            .preview
+            .render(&mut encoder, &wgpu_state.render_pipeline, background);
+        // The reason this is in a seperate block is that begin_render_pass() borrows encoder mutably (aka &mut self). We can't call encoder.finish() until we release that mutable borrow. If we don't do this then we get error `Command encoder is locked by a previously created render/compute pass. Before recording any new commands, the pass must be ended`. you can also use drop(render_pass) to achieve the same effect
+        {
+            // Encodes a single rendered pass of a screen
+            // RenderPassDescriptor describes the attachments of a render pass
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                // Debug Label
+                label: Some("Render Pass"),
+                // color_attachments describe where we are going to draw our color to
+                // RenderPassColorAttachment has view field, which informs wgpu what texture to save the colors to, a resolve_target is the texture that will receive the resolved output. This will be the same as view unless multisampling is enabled, the ops field takes a wgpu::Operations object. This tells wgpu what to do with the colors on the screen (specified by view).
+                // ! Color is the background color
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(background),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            /*
+            Indeces to reduce vertex count aren't working so something is likely misconfigured. Resolve later
+
+            let indices: [u8; 6] = [0, 1, 2, 1, 3, 2]; // Define the indices for two triangles. Indices are how the triangle orients vertex placement and overlap
+
+            // Create the index buffer
+            let index_buffer = wgpu_state.device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("Index Buffer"),
+                    contents: &indices,
+                    usage: wgpu::BufferUsages::INDEX,
+                },
+            );
+
+            // Set the index buffer to allow drawing a square with fewer or overlapping verticies
+            render_pass.set_index_buffer(
+                index_buffer.slice(..),
+                wgpu::IndexFormat::Uint32,
+            ); // Set the index buffer
+            */
+
+            // Set the render pipeline to integrate the shader
+            render_pass.set_pipeline(&wgpu_state.render_pipeline);
+            render_pass.set_bind_group(0, &wgpu_state.scene.bind_group, &[]);
+
+            // ! We tell wgpu to draw something with the given range of vertices and one instance. This is where @builtin(vertex_index) comes from.
+            render_pass.draw(0..6, 0..1);
+        }
+
+        // ! Can only use size one size otherwise it crashes
+        let screen_descriptor = ScreenDescriptor {
+            size_in_pixels: [wgpu_state.config.width, wgpu_state.config.height],
+            pixels_per_point: wgpu_state.window.scale_factor() as f32,
+        };
+
+        // Draws egui
+        let repaint_delay = wgpu_state.draw(&mut encoder, &view, screen_descriptor);
+
+        // Submits an iterator of the render command buffer to the queue
+        wgpu_state.queue.submit(std::iter::once(encoder.finish()));
+        // Schedule texture to be presented on the owned surface
+        output_texture.present();
+        // Keep frames coming while the view animates towards a preset or the preview spins
+        if animating {
+            return Ok(Duration::ZERO);
+        }
+        // The timer has to expire and its label count down even when egui has nothing else to draw
+        let session = &wgpu_state.egui.app_state_mut().session;
+        Ok(match session.until_next_second(Instant::now()) {
+            Some(next_second) => repaint_delay.min(next_second),
+            None => repaint_delay,
+        })
+    }
+}
+
+impl ApplicationHandler<UserEvent> for Win {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_none() {
+            match self.init(event_loop) {
+                Ok(()) => self.schedule_redraw(Duration::ZERO),
+                Err(e) => self.fail(event_loop, e),
+            }
+        }
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        _window_id: winit::window::WindowId,
+        event: winit::event::WindowEvent,
+    ) {
+        // Live input would fight the recording, so it only counts again once the replay has run out
+        if self.replay.is_some() && Input::from_event(&event).is_some() {
+            return;
+        }
+        if let Some(recorder) = self.recorder.as_mut()
+            && let Err(e) = recorder.record(&event)
+        {
+            log::error!("Stopped recording input: {e}");
+            self.recorder = None;
+        }
+        self.handle_window_event(event_loop, event);
+    }
+
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: UserEvent) {
+        match event {
+            UserEvent::AccessKit(event) => {
+                if let Some(wgpu_state) = self.wgpu_state.as_mut()
+                    && wgpu_state.egui.handle_accesskit(event.window_event)
+                {
+                    self.schedule_redraw(Duration::ZERO);
+                }
+            }
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.play_replay(event_loop);
+        if let Some(at) = self.redraw_at
+            && at <= Instant::now()
         {
-            wgpu_state.egui.handle_input(window, &event);
+            self.redraw_at = None;
+            // This is what actually causes the redraw event to be emitted
+            if let Some(window) = self.window.as_ref() {
+                window.request_redraw();
+            }
         }
+        // Sleep until the next frame or replayed input is due or an event arrives, whichever is first
+        let wake_at = [
+            self.redraw_at,
+            self.replay.as_ref().and_then(Replay::next_at),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        event_loop.set_control_flow(match wake_at {
+            Some(at) => ControlFlow::WaitUntil(at),
+            None => ControlFlow::Wait,
+        });
     }
 }